@@ -0,0 +1,153 @@
+use std::f32;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::framebuffer::to_io_error;
+use crate::sky::Sky;
+use crate::Vec3;
+
+use image::hdr::HdrDecoder;
+
+// What rays that don't hit anything see. Colors use the same 0 to 255 scale as materials and
+// lights, and the y axis points up.
+pub enum Background {
+    Color(Vec3),
+    // Blends from `bottom` looking straight down to `top` looking straight up
+    Gradient { top: Vec3, bottom: Vec3 },
+    Environment(EnvironmentMap),
+    Sky(Sky),
+}
+
+impl Background {
+    // Radiance arriving from direction `dir`, which has to be normalized
+    pub fn color(&self, dir: &Vec3) -> Vec3 {
+        match self {
+            Background::Color(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let t = (dir.y + 1.) / 2.;
+                bottom * (1. - t) + top * t
+            }
+            Background::Environment(map) => map.color(dir),
+            Background::Sky(sky) => sky.color(dir),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Color(Vec3::zeros())
+    }
+}
+
+// An equirectangular (latitude-longitude) image covering all directions. The center of the
+// image is in the +z direction, the top row is straight up.
+pub struct EnvironmentMap {
+    width: u32,
+    height: u32,
+    // Linear radiance, row by row, where 1 corresponds to 255
+    pixels: Vec<Vec3>,
+    intensity: f32,
+    // Rotation around the y axis in radians
+    rotation: f32,
+}
+
+impl EnvironmentMap {
+    // `rotation` is in degrees around the y axis
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3>, intensity: f32, rotation: f32) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        EnvironmentMap {
+            width,
+            height,
+            pixels,
+            intensity,
+            rotation: rotation.to_radians(),
+        }
+    }
+
+    // Loads the map from an HDR file (.hdr or .exr) with linear values, or any other image format
+    // supported by the image crate, which is assumed to be in sRGB. `rotation` is in degrees.
+    pub fn from_file(filename: &str, intensity: f32, rotation: f32) -> io::Result<Self> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let (width, height, pixels) = match extension.as_deref() {
+            Some("hdr") => {
+                let decoder =
+                    HdrDecoder::new(BufReader::new(File::open(filename)?)).map_err(to_io_error)?;
+                let metadata = decoder.metadata();
+                let pixels = decoder.read_image_hdr().map_err(to_io_error)?;
+                let pixels = pixels.iter().map(|p| Vec3::new(p[0], p[1], p[2])).collect();
+                (metadata.width, metadata.height, pixels)
+            }
+            Some("exr") => {
+                let image = exr::prelude::read_first_rgba_layer_from_file(
+                    filename,
+                    |resolution, _| (resolution.width(), vec![Vec3::zeros(); resolution.area()]),
+                    |(width, pixels): &mut (usize, Vec<Vec3>),
+                     position,
+                     (r, g, b, _): (f32, f32, f32, f32)| {
+                        pixels[position.y() * *width + position.x()] = Vec3::new(r, g, b);
+                    },
+                )
+                .map_err(to_io_error)?;
+                let size = image.layer_data.size;
+                let (_, pixels) = image.layer_data.channel_data.pixels;
+                (size.width() as u32, size.height() as u32, pixels)
+            }
+            _ => {
+                let image = image::open(filename).map_err(to_io_error)?.to_rgb();
+                let pixels = image
+                    .pixels()
+                    .map(|p| {
+                        Vec3::new(
+                            srgb_to_linear(p[0]),
+                            srgb_to_linear(p[1]),
+                            srgb_to_linear(p[2]),
+                        )
+                    })
+                    .collect();
+                (image.width(), image.height(), pixels)
+            }
+        };
+        Ok(EnvironmentMap::new(
+            width, height, pixels, intensity, rotation,
+        ))
+    }
+
+    fn color(&self, dir: &Vec3) -> Vec3 {
+        let longitude = dir.x.atan2(dir.z) - self.rotation;
+        let latitude = dir.y.clamp(-1., 1.).acos();
+        let u = 0.5 + longitude / (2. * f32::consts::PI);
+        let v = latitude / f32::consts::PI;
+        self.lookup(u, v) * (255. * self.intensity)
+    }
+
+    // Bilinear lookup, wrapping around horizontally
+    fn lookup(&self, u: f32, v: f32) -> Vec3 {
+        let x = (u - u.floor()) * self.width as f32 - 0.5;
+        let y = (v * self.height as f32 - 0.5).clamp(0., (self.height - 1) as f32);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let width = self.width as i64;
+        let column = |x: f32| (x as i64).rem_euclid(width) as u32;
+        let row = |y: f32| (y as u32).min(self.height - 1);
+        let pixel = |x, y| self.pixels[(y * self.width + x) as usize];
+        let (x0, x1) = (column(x0), column(x0 + 1.));
+        let (y0, y1) = (row(y0), row(y0 + 1.));
+
+        (pixel(x0, y0) * (1. - fx) + pixel(x1, y0) * fx) * (1. - fy)
+            + (pixel(x0, y1) * (1. - fx) + pixel(x1, y1) * fx) * fy
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.;
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
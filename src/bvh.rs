@@ -0,0 +1,337 @@
+use std::f32;
+
+use crate::ray::{Intersection, Ray};
+use crate::Vec3;
+
+// Number of buckets the centroids are binned into when evaluating split candidates
+const SAH_BUCKETS: usize = 12;
+// Relative cost of traversing an interior node compared to intersecting a primitive
+const TRAVERSAL_COST: f32 = 0.125;
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vec3::repeat(f32::INFINITY),
+            max: Vec3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |bbox, p| bbox.union(&Aabb::new(p, p)))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.x < 0. || d.y < 0. || d.z < 0. {
+            return 0.;
+        }
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // Slab test, returns the distance at which the ray enters the box if it does so before
+    // `max_dist`
+    fn intersect(&self, ray: &Ray, inv_dir: &Vec3, max_dist: f32) -> Option<f32> {
+        let mut t_min = 0.;
+        let mut t_max = max_dist;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inv_dir[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inv_dir[axis];
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            // Written so that NaNs (0 * inf) leave the interval unchanged
+            if t0 > t_min {
+                t_min = t0;
+            }
+            if t1 < t_max {
+                t_max = t1;
+            }
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
+
+enum Node {
+    Leaf {
+        bbox: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        bbox: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bbox(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bbox, .. } | Node::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+// A bounding volume hierarchy over a list of primitives, which are referred to by their index
+// in the list of bounding boxes the hierarchy was built from
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(boxes: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            indices: (0..boxes.len()).collect(),
+        };
+        if !boxes.is_empty() {
+            bvh.build(boxes, 0, boxes.len());
+        }
+        bvh
+    }
+
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| *node.bbox())
+    }
+
+    // Builds the subtree over `self.indices[start..end]` and returns the index of its root node
+    fn build(&mut self, boxes: &[Aabb], start: usize, end: usize) -> usize {
+        let bbox = self.indices[start..end]
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.union(&boxes[i]));
+        let node = self.nodes.len();
+        let count = end - start;
+        self.nodes.push(Node::Leaf { bbox, start, count });
+        if count <= 1 {
+            return node;
+        }
+
+        let centroid_bounds = Aabb::from_points(
+            self.indices[start..end]
+                .iter()
+                .map(|&i| boxes[i].centroid()),
+        );
+
+        // Find the cheapest split according to the surface area heuristic
+        let bucket_of = |axis: usize, i: usize| {
+            let lo = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - lo;
+            let b = ((boxes[i].centroid()[axis] - lo) / extent * SAH_BUCKETS as f32) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in 0..3 {
+            if centroid_bounds.max[axis] - centroid_bounds.min[axis] <= 0. {
+                continue;
+            }
+
+            let mut counts = [0; SAH_BUCKETS];
+            let mut bounds = [Aabb::empty(); SAH_BUCKETS];
+            for &i in self.indices[start..end].iter() {
+                let b = bucket_of(axis, i);
+                counts[b] += 1;
+                bounds[b] = bounds[b].union(&boxes[i]);
+            }
+
+            for split in 1..SAH_BUCKETS {
+                let (left_count, left_bounds) = (0..split).fold((0, Aabb::empty()), |(c, b), j| {
+                    (c + counts[j], b.union(&bounds[j]))
+                });
+                let (right_count, right_bounds) = (split..SAH_BUCKETS)
+                    .fold((0, Aabb::empty()), |(c, b), j| {
+                        (c + counts[j], b.union(&bounds[j]))
+                    });
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = TRAVERSAL_COST
+                    + (left_count as f32 * left_bounds.surface_area()
+                        + right_count as f32 * right_bounds.surface_area())
+                        / bbox.surface_area();
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let (cost, axis, split) = match best {
+            Some(best) => best,
+            // All centroids coincide, there is nothing to split on
+            None => return node,
+        };
+        if count <= MAX_LEAF_SIZE && cost >= count as f32 {
+            return node;
+        }
+
+        // Partition with the same bucketing the costs were computed with, so both sides get the
+        // primitives that were counted for them
+        let mut mid = start;
+        for i in start..end {
+            if bucket_of(axis, self.indices[i]) < split {
+                self.indices.swap(i, mid);
+                mid += 1;
+            }
+        }
+        if mid == start || mid == end {
+            // Shouldn't happen since both sides were counted as non-empty, but an empty side
+            // would recurse forever, so split at the median instead
+            mid = start + count / 2;
+        }
+
+        let left = self.build(boxes, start, mid);
+        let right = self.build(boxes, mid, end);
+        self.nodes[node] = Node::Interior { bbox, left, right };
+        node
+    }
+
+    // Finds the closest intersection along the ray. `intersect` is called with the index of
+    // each primitive whose bounding box is hit.
+    pub fn intersect<F>(&self, ray: &Ray, mut intersect: F) -> Option<(usize, Intersection)>
+    where
+        F: FnMut(usize) -> Option<Intersection>,
+    {
+        let mut result: Option<(usize, Intersection)> = None;
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let inv_dir = Vec3::new(1. / ray.dir.x, 1. / ray.dir.y, 1. / ray.dir.z);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let max_dist = result.as_ref().map_or(f32::INFINITY, |(_, hit)| hit.dist);
+            if self.nodes[node]
+                .bbox()
+                .intersect(ray, &inv_dir, max_dist)
+                .is_none()
+            {
+                continue;
+            }
+
+            match self.nodes[node] {
+                Node::Leaf { start, count, .. } => {
+                    for &i in self.indices[start..start + count].iter() {
+                        if let Some(hit) = intersect(i) {
+                            match result {
+                                Some((_, ref old_hit)) if old_hit.dist <= hit.dist => (),
+                                _ => result = Some((i, hit)),
+                            }
+                        }
+                    }
+                }
+                Node::Interior { left, right, .. } => {
+                    // Push the farther child first so the nearer one is visited first
+                    let left_dist = self.nodes[left].bbox().intersect(ray, &inv_dir, max_dist);
+                    let right_dist = self.nodes[right].bbox().intersect(ray, &inv_dir, max_dist);
+                    match (left_dist, right_dist) {
+                        (Some(l), Some(r)) if l < r => stack.extend(&[right, left]),
+                        (Some(_), Some(_)) => stack.extend(&[left, right]),
+                        (Some(_), None) => stack.push(left),
+                        (None, Some(_)) => stack.push(right),
+                        (None, None) => (),
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::SamplerType;
+
+    // Nearest hit of the ray on a sphere, outside of it
+    fn intersect_sphere(ray: &Ray, center: &Vec3, radius: f32) -> Option<Intersection> {
+        let offset = ray.origin - center;
+        let b = offset.dot(&ray.dir);
+        let discriminant = b * b - offset.norm_squared() + radius * radius;
+        if discriminant < 0. {
+            return None;
+        }
+        let dist = -b - discriminant.sqrt();
+        if dist <= 0. {
+            return None;
+        }
+        let pos = ray.origin + ray.dir * dist;
+        Some(Intersection {
+            pos,
+            normal: (pos - center) / radius,
+            dist,
+            u: 0.,
+            v: 0.,
+        })
+    }
+
+    #[test]
+    fn nearest_hit_matches_a_linear_scan() {
+        let mut sampler = SamplerType::Independent.new_sampler(1, 7);
+        let mut random_vec = |scale: f32| {
+            let (x, y) = sampler.next_2d();
+            let z = sampler.next_1d();
+            Vec3::new(x - 0.5, y - 0.5, z - 0.5) * scale
+        };
+        let spheres: Vec<(Vec3, f32)> = (0..200)
+            .map(|_| (random_vec(10.), random_vec(1.).x.abs() + 0.1))
+            .collect();
+        let boxes: Vec<Aabb> = spheres
+            .iter()
+            .map(|(center, radius)| {
+                let extent = Vec3::new(*radius, *radius, *radius);
+                Aabb::new(center - extent, center + extent)
+            })
+            .collect();
+        let bvh = Bvh::new(&boxes);
+
+        let mut hits = 0;
+        for k in 0..500 {
+            // Aim near a sphere so that most rays hit something
+            let origin = random_vec(30.);
+            let target = spheres[k % spheres.len()].0 + random_vec(0.5);
+            let dir = (target - origin).normalize();
+            let ray = Ray::new(origin, dir);
+            let bvh_hit = bvh.intersect(&ray, |i| {
+                let (center, radius) = (spheres[i].0, spheres[i].1);
+                intersect_sphere(&ray, &center, radius)
+            });
+            let linear_hit = spheres
+                .iter()
+                .enumerate()
+                .filter_map(|(i, (center, radius))| {
+                    intersect_sphere(&ray, center, *radius).map(|hit| (i, hit))
+                })
+                .min_by(|(_, a), (_, b)| a.dist.partial_cmp(&b.dist).unwrap());
+            let bvh_hit = bvh_hit.map(|(i, hit)| (i, hit.dist));
+            assert_eq!(bvh_hit, linear_hit.map(|(i, hit)| (i, hit.dist)));
+            hits += bvh_hit.is_some() as u32;
+        }
+        // Make sure the rays aren't all misses
+        assert!(hits > 250);
+    }
+}
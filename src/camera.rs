@@ -0,0 +1,251 @@
+use std::f32;
+
+use crate::ray::Ray;
+use crate::Vec3;
+
+use nalgebra::{Matrix3, Matrix4, Orthographic3, Perspective3};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Fov {
+    // Angle in degrees between the top and bottom edges of the image
+    Vertical(f32),
+    // Angle in degrees between the left and right edges of the image
+    Horizontal(f32),
+}
+
+impl Fov {
+    // Half the height of the image plane at unit distance from the camera
+    fn half_height(&self, aspect_ratio: f32) -> f32 {
+        match *self {
+            Fov::Vertical(fov) => (fov.to_radians() / 2.).tan(),
+            Fov::Horizontal(fov) => (fov.to_radians() / 2.).tan() / aspect_ratio,
+        }
+    }
+}
+
+impl Default for Fov {
+    // An image plane one unit high at unit distance, about 53 degrees
+    fn default() -> Self {
+        Fov::Vertical(2. * 0.5f32.atan().to_degrees())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Projection {
+    // Pinhole (or thin lens) camera
+    Perspective(Fov),
+    // Parallel rays along the view direction, `size` is the height of the image in world units
+    Orthographic { size: f32 },
+    // Equidistant fisheye, the angle from the view direction grows linearly with the distance
+    // from the image center. `fov` in degrees spans the shorter side of the image, pixels
+    // outside of that circle are black.
+    Fisheye { fov: f32 },
+    // Full 360 by 180 degree panorama with longitude along x and latitude along y
+    Equirectangular,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective(Fov::default())
+    }
+}
+
+// The scene uses left-handed coordinates: looking along `dir` with `up` pointing up, the x axis
+// of the image is `up x dir`.
+#[derive(Debug)]
+pub struct Camera {
+    pos: Vec3,
+    dir: Vec3,
+    up: Vec3,
+    right: Vec3,
+    projection: Projection,
+    // Diameter of the lens, 0 for a pinhole camera with everything in focus
+    aperture: f32,
+    // Distance along `dir` of the plane that is in perfect focus
+    focus_distance: f32,
+}
+
+impl Camera {
+    pub fn new(
+        pos: Vec3,
+        dir: Vec3,
+        up: Vec3,
+        projection: Projection,
+        aperture: f32,
+        focus_distance: f32,
+    ) -> Self {
+        let dir = dir.normalize();
+        let right = up.cross(&dir).normalize();
+        // `up` doesn't have to be perpendicular to `dir`, use the closest vector that is
+        let up = dir.cross(&right).normalize();
+        Camera {
+            pos,
+            up,
+            right,
+            dir,
+            projection,
+            aperture,
+            focus_distance,
+        }
+    }
+
+    pub fn from_lookat(
+        pos: Vec3,
+        lookat: Vec3,
+        up: Vec3,
+        projection: Projection,
+        aperture: f32,
+        focus_distance: f32,
+    ) -> Self {
+        let dir = lookat - pos;
+        Camera::new(pos, dir, up, projection, aperture, focus_distance)
+    }
+
+    pub fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    // Field of view in degrees between the top and bottom edges of the image, for perspective
+    // projections
+    pub fn vertical_fov(&self, aspect_ratio: f32) -> Option<f32> {
+        match self.projection {
+            Projection::Perspective(fov) => {
+                Some(2. * fov.half_height(aspect_ratio).atan().to_degrees())
+            }
+            _ => None,
+        }
+    }
+
+    // Field of view in degrees between the left and right edges of the image, for perspective
+    // projections
+    pub fn horizontal_fov(&self, aspect_ratio: f32) -> Option<f32> {
+        match self.projection {
+            Projection::Perspective(fov) => Some(
+                2. * (fov.half_height(aspect_ratio) * aspect_ratio)
+                    .atan()
+                    .to_degrees(),
+            ),
+            _ => None,
+        }
+    }
+
+    // World to camera transform in the OpenGL convention (x right, y up, looking down -z), so
+    // it can be combined with `projection_matrix`
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let rotation = Matrix3::from_rows(&[
+            self.right.transpose(),
+            self.up.transpose(),
+            -self.dir.transpose(),
+        ]);
+        rotation.to_homogeneous() * Matrix4::new_translation(&-self.pos)
+    }
+
+    // OpenGL style projection matching the rendered image. Normalized device coordinates (x, y)
+    // correspond to the pixel ((x + 1) / 2 * width, (1 - y) / 2 * height). Fisheye and
+    // panoramic projections can't be expressed as a matrix.
+    pub fn projection_matrix(
+        &self,
+        aspect_ratio: f32,
+        znear: f32,
+        zfar: f32,
+    ) -> Option<Matrix4<f32>> {
+        match self.projection {
+            Projection::Perspective(fov) => {
+                let fovy = 2. * fov.half_height(aspect_ratio).atan();
+                Some(Perspective3::new(aspect_ratio, fovy, znear, zfar).to_homogeneous())
+            }
+            Projection::Orthographic { size } => {
+                let (half_width, half_height) = (size * aspect_ratio / 2., size / 2.);
+                Some(
+                    Orthographic3::new(
+                        -half_width,
+                        half_width,
+                        -half_height,
+                        half_height,
+                        znear,
+                        zfar,
+                    )
+                    .to_homogeneous(),
+                )
+            }
+            Projection::Fisheye { .. } | Projection::Equirectangular => None,
+        }
+    }
+
+    // (`x`, `y`) is a position in the image in pixels, with (0, 0) the top left corner of the
+    // image. `lens_sample` is a pair of uniform samples used to pick the point on the lens the ray
+    // starts from. Returns `None` for points the projection doesn't cover.
+    pub(crate) fn get_ray(
+        &self,
+        x: f32,
+        y: f32,
+        width: u32,
+        height: u32,
+        aspect_ratio: f32,
+        lens_sample: (f32, f32),
+    ) -> Option<Ray> {
+        // Position in the image with the center at 0 and the top and bottom edges at 0.5 and
+        // -0.5
+        let norm_x = ((x / width as f32) - 0.5) * aspect_ratio;
+        let norm_y = 0.5 - (y / height as f32);
+
+        let dir = match self.projection {
+            Projection::Perspective(fov) => {
+                let half_height = fov.half_height(aspect_ratio);
+                self.right * norm_x * 2. * half_height
+                    + self.up * norm_y * 2. * half_height
+                    + self.dir
+            }
+            Projection::Orthographic { size } => {
+                let origin = self.pos + (self.right * norm_x + self.up * norm_y) * size;
+                return Some(Ray::new(origin, self.dir));
+            }
+            Projection::Fisheye { fov } => {
+                // Scale so the shorter side of the image goes from -1 to 1
+                let scale = 2. * f32::max(1., aspect_ratio) / aspect_ratio;
+                let (px, py) = (norm_x * scale, norm_y * scale);
+                let r = (px * px + py * py).sqrt();
+                if r > 1. {
+                    return None;
+                }
+                let theta = r * fov.to_radians() / 2.;
+                let phi = py.atan2(px);
+                (self.right * phi.cos() + self.up * phi.sin()) * theta.sin()
+                    + self.dir * theta.cos()
+            }
+            Projection::Equirectangular => {
+                let longitude = norm_x / aspect_ratio * 2. * f32::consts::PI;
+                let latitude = norm_y * f32::consts::PI;
+                (self.right * longitude.sin() + self.dir * longitude.cos()) * latitude.cos()
+                    + self.up * latitude.sin()
+            }
+        };
+        if self.aperture <= 0. {
+            return Some(Ray::new(self.pos, dir));
+        }
+
+        // All rays through the lens for this pixel converge on the same point. Perspective
+        // directions are one unit along `dir` so they focus on a plane, the other projections
+        // have unit directions and focus on a sphere around the camera.
+        let focus_point = self.pos + dir * self.focus_distance;
+        let (lens_x, lens_y) = sample_disk(lens_sample.0, lens_sample.1);
+        let origin = self.pos + (self.right * lens_x + self.up * lens_y) * (self.aperture / 2.);
+        Some(Ray::new(origin, focus_point - origin))
+    }
+}
+
+// Maps two uniform samples to a point on the unit disk, using the concentric mapping which keeps
+// stratified samples well distributed
+fn sample_disk(u1: f32, u2: f32) -> (f32, f32) {
+    let a = 2. * u1 - 1.;
+    let b = 2. * u2 - 1.;
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, f32::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, f32::consts::FRAC_PI_2 - f32::consts::FRAC_PI_4 * (a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
@@ -0,0 +1,353 @@
+use serde::{Deserialize, Serialize};
+
+// Plain data mirroring the scene files in `scenes/`, which can be deserialized from and
+// serialized back to a file, with `scene_loader` turning it into a `Scene`. Optional keys with a
+// default are filled in when deserializing, so serializing a description gives the normalized
+// scene with every setting spelled out.
+//
+// A whole scene file is `[[material]]` tables followed by the `[scene]` table.
+//
+// The tables picked by their `type` are type parameters so they can be read as plain values
+// first. Deserializing them directly loses track of where in the table an error is, so the
+// loader reads the `type` itself and then the fields of the matching variant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription<S = SurfaceDescription, L = LightDescription, B = BackgroundDescription>
+{
+    #[serde(rename = "material", default, skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<MaterialDescription>,
+    pub scene: WorldDescription<S, L, B>,
+}
+
+// The `[scene]` table. Plain values come before tables, which TOML needs for serialization.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldDescription<S = SurfaceDescription, L = LightDescription, B = BackgroundDescription>
+{
+    pub ambient_const: f32,
+    pub ambient_color: [f32; 3],
+    pub camera: CameraDescription,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<B>,
+    #[serde(
+        rename = "surface",
+        default = "Vec::new",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub surfaces: Vec<S>,
+    #[serde(
+        rename = "light",
+        default = "Vec::new",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub lights: Vec<L>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+    pub name: String,
+    pub color: [f32; 3],
+    pub diffuse: f32,
+    pub specular: f32,
+    pub glossiness: f32,
+    pub reflectivity: f32,
+    #[serde(default)]
+    pub transparency: f32,
+    #[serde(default = "one")]
+    pub ior: f32,
+    #[serde(default)]
+    pub emission: [f32; 3],
+    #[serde(default = "one")]
+    pub emission_strength: f32,
+    // Size of the squares of a checkerboard texture, which takes precedence over `texture`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkerboard: Option<f32>,
+    // Image file for the texture
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub texture: Option<String>,
+    // Noise parameters: seed, octaves, wavelength, persistence and lacunarity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal_map: Option<[f32; 5]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub displacement_map: Option<[f32; 5]>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionType {
+    #[default]
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FovAxis {
+    #[default]
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub pos: [f32; 3],
+    pub lookat: [f32; 3],
+    pub up: [f32; 3],
+    #[serde(default)]
+    pub projection: ProjectionType,
+    // In degrees, the default depends on the projection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fov: Option<f32>,
+    #[serde(default)]
+    pub fov_axis: FovAxis,
+    // Height of the view of an orthographic camera
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ortho_size: Option<f32>,
+    #[serde(default)]
+    pub aperture: f32,
+    // The distance to `lookat` by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f32>,
+}
+
+// The variants are the names in the `type` key
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundDescription {
+    Color(ColorBackgroundDescription),
+    Gradient(GradientBackgroundDescription),
+    Environment(EnvironmentDescription),
+    Sky(SkyDescription),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorBackgroundDescription {
+    pub color: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GradientBackgroundDescription {
+    pub top: [f32; 3],
+    pub bottom: [f32; 3],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDescription {
+    pub file: String,
+    #[serde(default = "one")]
+    pub intensity: f32,
+    // Degrees around the vertical axis
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SkyDescription {
+    pub sun_elevation: f32,
+    #[serde(default)]
+    pub sun_azimuth: f32,
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f32,
+    #[serde(default = "one")]
+    pub intensity: f32,
+    // Adds a directional light for the sun
+    #[serde(default)]
+    pub sun_light: bool,
+    #[serde(default = "one")]
+    pub sun_intensity: f32,
+}
+
+// Every surface can have a `transform` table placing it in the scene
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SurfaceDescription {
+    Plane(PlaneDescription),
+    Sphere(SphereDescription),
+    Mesh(MeshDescription),
+}
+
+impl SurfaceDescription {
+    pub fn material(&self) -> &str {
+        match self {
+            SurfaceDescription::Plane(plane) => &plane.material,
+            SurfaceDescription::Sphere(sphere) => &sphere.material,
+            SurfaceDescription::Mesh(mesh) => &mesh.material,
+        }
+    }
+
+    pub fn transform(&self) -> Option<&TransformDescription> {
+        match self {
+            SurfaceDescription::Plane(plane) => plane.transform.as_ref(),
+            SurfaceDescription::Sphere(sphere) => sphere.transform.as_ref(),
+            SurfaceDescription::Mesh(mesh) => mesh.transform.as_ref(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneDescription {
+    pub material: String,
+    pub pos: [f32; 3],
+    pub normal: [f32; 3],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SphereDescription {
+    pub material: String,
+    pub pos: [f32; 3],
+    pub radius: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformDescription>,
+}
+
+// A Wavefront OBJ file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeshDescription {
+    pub material: String,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<TransformDescription>,
+}
+
+// The surface is scaled, rotated and translated in that order, and then transformed by `matrix`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformDescription {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<ScaleDescription>,
+    // Degrees around the x, y and z axes, applied in that order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translate: Option<[f32; 3]>,
+    // Rows of an affine transformation matrix, with [0, 0, 0, 1] at the bottom
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<[[f32; 4]; 4]>,
+}
+
+// Either the same factor along every axis or one per axis
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScaleDescription {
+    Uniform(f32),
+    Axes([f32; 3]),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttenuationType {
+    None,
+    Linear,
+    InverseSquare,
+}
+
+// Either the name of a falloff or the [constant, linear, quadratic] coefficients
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AttenuationDescription {
+    Named(AttenuationType),
+    Coefficients([f32; 3]),
+}
+
+impl Default for AttenuationDescription {
+    fn default() -> Self {
+        AttenuationDescription::Named(AttenuationType::None)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AreaShapeType {
+    // Spanned by the `u` and `v` edges
+    Rect,
+    // With a `radius`
+    Sphere,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LightDescription {
+    Point(PointLightDescription),
+    Area(AreaLightDescription),
+    Directional(DirectionalLightDescription),
+    Spot(SpotLightDescription),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightDescription {
+    pub pos: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    #[serde(default)]
+    pub attenuation: AttenuationDescription,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AreaLightDescription {
+    // The center of the shape
+    pub pos: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    #[serde(default = "default_area_samples")]
+    pub samples: u32,
+    pub shape: AreaShapeType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub u: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub attenuation: AttenuationDescription,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightDescription {
+    pub dir: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpotLightDescription {
+    pub pos: [f32; 3],
+    pub dir: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+    // In degrees, full intensity inside the inner angle and none outside the outer one
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    #[serde(default = "one")]
+    pub falloff: f32,
+    #[serde(default)]
+    pub attenuation: AttenuationDescription,
+}
+
+fn one() -> f32 {
+    1.
+}
+
+fn default_turbidity() -> f32 {
+    3.
+}
+
+fn default_area_samples() -> u32 {
+    16
+}
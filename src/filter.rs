@@ -0,0 +1,131 @@
+use std::f32;
+
+// Number of entries in the table used to sample the filter
+const TABLE_SIZE: usize = 64;
+
+// Reconstruction filters used to weight the samples of a pixel. Samples are spread over a square
+// of `radius` pixels around the pixel center, all filters are separable.
+#[derive(Clone, Copy, Debug)]
+pub enum Filter {
+    Box { radius: f32 },
+    Tent { radius: f32 },
+    Gaussian { radius: f32 },
+    // Mitchell-Netravali with B = C = 1/3
+    Mitchell { radius: f32 },
+}
+
+// Falloff of the Gaussian filter
+const GAUSSIAN_ALPHA: f32 = 2.;
+const MITCHELL_B: f32 = 1. / 3.;
+const MITCHELL_C: f32 = 1. / 3.;
+
+impl Filter {
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius }
+            | Filter::Mitchell { radius } => radius,
+        }
+    }
+
+    // Weight of a sample at an offset of (`dx`, `dy`) pixels from the pixel center
+    pub fn weight(&self, dx: f32, dy: f32) -> f32 {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(&self, x: f32) -> f32 {
+        match *self {
+            Filter::Box { .. } => 1.,
+            Filter::Tent { radius } => tent(x, radius),
+            Filter::Gaussian { radius } => gaussian(x, radius),
+            Filter::Mitchell { radius } => mitchell(x / radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    // Averages the samples within the pixel
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+fn tent(x: f32, radius: f32) -> f32 {
+    f32::max(0., radius - x.abs())
+}
+
+// Shifted down so the filter goes to zero at the radius
+fn gaussian(x: f32, radius: f32) -> f32 {
+    f32::max(
+        0.,
+        (-GAUSSIAN_ALPHA * x * x).exp() - (-GAUSSIAN_ALPHA * radius * radius).exp(),
+    )
+}
+
+// `x` is scaled so the filter covers [-1, 1]
+fn mitchell(x: f32) -> f32 {
+    let (b, c) = (MITCHELL_B, MITCHELL_C);
+    let x = (2. * x).abs();
+    if x > 2. {
+        0.
+    } else if x > 1. {
+        ((-b - 6. * c) * x * x * x
+            + (6. * b + 30. * c) * x * x
+            + (-12. * b - 48. * c) * x
+            + (8. * b + 24. * c))
+            / 6.
+    } else {
+        ((12. - 9. * b - 6. * c) * x * x * x + (-18. + 12. * b + 6. * c) * x * x + (6. - 2. * b))
+            / 6.
+    }
+}
+
+// Distributes samples proportionally to the magnitude of the filter, so every sample has a weight
+// of 1 or -1. Weighting uniformly distributed samples instead gets very noisy for filters with
+// negative lobes, where the sum of the weights can get close to 0.
+pub(crate) struct FilterSampler {
+    radius: f32,
+    // Cumulative distribution of |f| over TABLE_SIZE equal bins covering [-radius, radius]
+    cdf: Vec<f32>,
+    signs: Vec<f32>,
+}
+
+impl FilterSampler {
+    pub(crate) fn new(filter: &Filter) -> Self {
+        let radius = filter.radius();
+        let bin_width = 2. * radius / TABLE_SIZE as f32;
+
+        let mut cdf = vec![0.];
+        let mut signs = Vec::new();
+        for i in 0..TABLE_SIZE {
+            let x = -radius + (i as f32 + 0.5) * bin_width;
+            let f = filter.weight_1d(x);
+            cdf.push(cdf[i] + f.abs());
+            signs.push(if f < 0. { -1. } else { 1. });
+        }
+        let total = cdf[TABLE_SIZE];
+        for c in cdf.iter_mut() {
+            *c /= total;
+        }
+
+        FilterSampler { radius, cdf, signs }
+    }
+
+    // Maps a uniform sample to an offset from the pixel center along one axis, and the weight of
+    // the sample along that axis
+    pub(crate) fn sample(&self, u: f32) -> (f32, f32) {
+        // Find the bin containing u by skipping the bins that end before it, empty ones included
+        let i = self.cdf[1..].iter().take_while(|&&c| c <= u).count();
+        let i = i.min(TABLE_SIZE - 1);
+        let bin_size = self.cdf[i + 1] - self.cdf[i];
+        let t = if bin_size > 0. {
+            (u - self.cdf[i]) / bin_size
+        } else {
+            0.5
+        };
+
+        let x = -self.radius + (i as f32 + t) * 2. * self.radius / TABLE_SIZE as f32;
+        (x, self.signs[i])
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::tonemap::{luminance, ToneMapper};
+use crate::Vec3;
+
+use image::hdr::HdrEncoder;
+use image::{Pixel, Rgb, RgbImage};
+
+// Linear radiance of every pixel, where 1 corresponds to a color channel of 255 in the scene
+// description. Values aren't clamped, so highlights keep their full range.
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Vec3>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Vec3::zeros(); (width * height) as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Vec3 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn put(&mut self, x: u32, y: u32, color: Vec3) {
+        self.pixels[(y * self.width + x) as usize] = color;
+    }
+
+    // Converts to 8 bits per channel with the tone mapper
+    pub fn to_rgb8(&self, tone_mapper: &ToneMapper) -> RgbImage {
+        let max_luminance = self.pixels.iter().map(luminance).fold(0., f32::max);
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            // Rounded, truncating would make colors that come out a hair below a whole level after
+            // the division by 255 one level darker
+            let color = (tone_mapper.map(&self.get(x, y), max_luminance) * 255.).map(f32::round);
+            Rgb::from_channels(color.x as u8, color.y as u8, color.z as u8, 255)
+        })
+    }
+
+    // The extension picks the format. OpenEXR (.exr), Radiance (.hdr) and PFM (.pfm) files get
+    // the linear floating point values, any other format supported by the image crate gets the
+    // tone mapped 8-bit image.
+    pub fn save(&self, filename: &str, tone_mapper: &ToneMapper) -> io::Result<()> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("exr") => self.save_exr(filename),
+            Some("hdr") => self.save_hdr(filename),
+            Some("pfm") => self.save_pfm(filename),
+            _ => self
+                .to_rgb8(tone_mapper)
+                .save(filename)
+                .map_err(to_io_error),
+        }
+    }
+
+    fn save_exr(&self, filename: &str) -> io::Result<()> {
+        exr::prelude::write_rgb_file(
+            filename,
+            self.width as usize,
+            self.height as usize,
+            |x, y| {
+                let color = self.get(x as u32, y as u32);
+                (color.x, color.y, color.z)
+            },
+        )
+        .map_err(to_io_error)
+    }
+
+    fn save_hdr(&self, filename: &str) -> io::Result<()> {
+        let pixels: Vec<_> = self
+            .pixels
+            .iter()
+            .map(|c| Rgb::from_channels(c.x.max(0.), c.y.max(0.), c.z.max(0.), 0.))
+            .collect();
+        let file = BufWriter::new(File::create(filename)?);
+        HdrEncoder::new(file)
+            .encode(&pixels, self.width as usize, self.height as usize)
+            .map_err(to_io_error)
+    }
+
+    // Portable float map: a text header followed by little endian floats, with the rows stored
+    // from the bottom of the image to the top
+    fn save_pfm(&self, filename: &str) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(filename)?);
+        // A negative scale marks the data as little endian
+        write!(file, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.get(x, y);
+                for c in color.iter() {
+                    file.write_all(&c.to_le_bytes())?;
+                }
+            }
+        }
+        file.flush()
+    }
+}
+
+pub(crate) fn to_io_error(e: impl ToString) -> io::Error {
+    io::Error::other(e.to_string())
+}
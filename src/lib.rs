@@ -1,92 +1,132 @@
+pub mod background;
+mod bvh;
+pub mod camera;
+pub mod description;
+pub mod filter;
+pub mod framebuffer;
 pub mod light;
 pub mod material;
+pub mod obj;
 mod ray;
+pub mod sampler;
+pub mod scene_loader;
+pub mod sky;
 pub mod surface;
 pub mod texture;
+pub mod tonemap;
 
 use std::f32;
 
-use light::PointLight;
+use background::Background;
+use bvh::Bvh;
+use camera::Camera;
+use filter::{Filter, FilterSampler};
+use framebuffer::Framebuffer;
+use light::Light;
+use material::Material;
 use ray::{Intersection, Ray};
+use sampler::{Sampler, SamplerType};
 use surface::Surface;
+use tonemap::luminance;
 
 use image::{Pixel, Rgb, RgbImage};
 
 use nalgebra::clamp;
 
-pub type Vec3 = nalgebra::Vector3<f32>;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 
-#[derive(Debug)]
-pub struct Camera {
-    pos: Vec3,
-    dir: Vec3,
-    up: Vec3,
-    right: Vec3,
-}
-
-impl Camera {
-    pub fn new(pos: Vec3, dir: Vec3, up: Vec3) -> Self {
-        let right = up.cross(&dir).normalize();
-        let up = right.cross(&dir).normalize();
-        Camera {
-            pos,
-            up,
-            right,
-            dir: dir.normalize(),
-        }
-    }
+use serde::{Deserialize, Serialize};
 
-    pub fn from_lookat(pos: Vec3, lookat: Vec3, up: Vec3) -> Self {
-        let dir = lookat - pos;
-        Camera::new(pos, dir, up)
-    }
+// Width and height in pixels of the tiles the image is split into for rendering
+const TILE_SIZE: u32 = 16;
+// Bounces after which Russian roulette starts terminating paths
+const ROULETTE_DEPTH: u32 = 3;
 
-    fn get_ray(&self, x: u32, y: u32, width: u32, height: u32, aspect_ratio: f32) -> Ray {
-        let norm_x = (x as f32 / width as f32) - 0.5;
-        let norm_y = (y as f32 / height as f32) - 0.5;
-        let norm_x = norm_x * aspect_ratio;
+pub type Vec3 = nalgebra::Vector3<f32>;
 
-        let dir = self.right * norm_x + self.up * norm_y + self.dir;
-        Ray::new(self.pos, dir)
-    }
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    // Recursive ray tracing with direct lighting, perfect reflection and refraction, and a
+    // constant ambient term
+    Whitted,
+    // Monte Carlo path tracing, tracing one path per pixel sample
+    Path,
 }
 
 pub struct Scene {
     objects: Vec<Box<dyn Surface>>,
-    lights: Vec<PointLight>,
+    // Indices into `objects`, split by whether the object can be put in the BVH
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+    bvh: Bvh,
+    lights: Vec<Box<dyn Light>>,
+    // Whether each object's emission is sampled as one of `lights`
+    sampled_emitters: Vec<bool>,
     ambient_coeff: f32,
     ambient_color: Vec3,
+    background: Background,
     camera: Camera,
 }
 
 impl Scene {
     pub fn new(
         objects: Vec<Box<dyn Surface>>,
-        lights: Vec<PointLight>,
+        mut lights: Vec<Box<dyn Light>>,
         ambient_coeff: f32,
         ambient_color: Vec3,
+        background: Background,
         camera: Camera,
     ) -> Self {
+        // Emissive objects light the scene like any other light
+        let mut sampled_emitters = Vec::new();
+        for obj in objects.iter() {
+            let light = obj.light();
+            sampled_emitters.push(light.is_some());
+            if let Some(light) = light {
+                lights.push(Box::new(light));
+            }
+        }
+
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut boxes = Vec::new();
+        for (i, obj) in objects.iter().enumerate() {
+            match obj.bounding_box() {
+                Some(bbox) => {
+                    bounded.push(i);
+                    boxes.push(bbox);
+                }
+                None => unbounded.push(i),
+            }
+        }
+
         Scene {
             objects,
+            bounded,
+            unbounded,
+            bvh: Bvh::new(&boxes),
             lights,
+            sampled_emitters,
             ambient_coeff,
             ambient_color,
+            background,
             camera,
         }
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<(&Box<dyn Surface>, Intersection)> {
-        let mut result = None;
-        for obj in self.objects.iter() {
-            if let Some(hit) = obj.intersect(ray) {
-                match result.clone() {
-                    None => result = Some((obj, hit)),
-                    Some((_, ref old_hit)) => {
-                        if hit.dist < old_hit.dist {
-                            result = Some((obj, hit))
-                        }
-                    }
+    // Returns the index of the closest object hit by the ray and the intersection
+    fn intersect(&self, ray: &Ray) -> Option<(usize, Intersection)> {
+        let mut result = self
+            .bvh
+            .intersect(ray, |i| self.objects[self.bounded[i]].intersect(ray))
+            .map(|(i, hit)| (self.bounded[i], hit));
+        for &i in self.unbounded.iter() {
+            if let Some(hit) = self.objects[i].intersect(ray) {
+                match result {
+                    Some((_, ref old_hit)) if old_hit.dist <= hit.dist => (),
+                    _ => result = Some((i, hit)),
                 }
             }
         }
@@ -94,75 +134,464 @@ impl Scene {
     }
 }
 
-pub fn ray_trace(scene: &Scene, width: u32, height: u32, max_depth: u16) -> RgbImage {
-    let aspect_ratio = width as f32 / height as f32;
+#[derive(Clone, Copy, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    // Maximum recursion depth for reflected and refracted rays of the Whitted integrator
+    pub max_depth: u16,
+    // Maximum number of bounces of a path, a hard limit in case roulette keeps a path alive for
+    // too long
+    pub path_depth: u32,
+    // Number of worker threads, 0 uses one per core
+    pub threads: usize,
+    pub integrator: Integrator,
+    // Camera rays traced per pixel, each through a different point of the pixel and the lens.
+    // With adaptive sampling this is the number of samples taken before checking the noise.
+    pub samples: u32,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub filter: Filter,
+    pub sampler: SamplerType,
+    // Seed for the sampler, the same seed gives the same image
+    pub seed: u64,
+}
+
+impl RenderSettings {
+    // Largest number of samples a pixel can get
+    pub fn max_samples(&self) -> u32 {
+        self.adaptive.map_or(self.samples, |a| {
+            a.max_samples
+                .unwrap_or(self.samples.saturating_mul(16))
+                .max(self.samples)
+        })
+    }
+}
 
-    let mut im: RgbImage = RgbImage::new(width, height);
-    for x in 0..width {
-        for y in 0..height {
-            let ray = scene.camera.get_ray(x, y, width, height, aspect_ratio);
-            let color = trace_ray(&scene, &ray, 0, max_depth);
-
-            let color = Rgb::from_channels(
-                clamp(color.x, 0., 255.) as u8,
-                clamp(color.y, 0., 255.) as u8,
-                clamp(color.z, 0., 255.) as u8,
-                255,
-            );
-            im.put_pixel(x, y, color);
+impl Default for RenderSettings {
+    // One sample per pixel at 640x480 with a single bounce
+    fn default() -> Self {
+        RenderSettings {
+            width: 640,
+            height: 480,
+            max_depth: 1,
+            path_depth: 64,
+            threads: 0,
+            integrator: Integrator::Whitted,
+            samples: 1,
+            adaptive: None,
+            filter: Filter::default(),
+            sampler: SamplerType::Stratified,
+            seed: 0,
         }
     }
-    im
-}
-
-fn trace_ray(scene: &Scene, ray: &Ray, depth: u16, max_depth: u16) -> Vec3 {
-    let mut color = Vec3::new(0., 0., 0.); // TODO: Background color
-    if let Some((obj, hit)) = scene.intersect(ray) {
-        let material = obj.material();
-
-        // Ambient color
-        color = material
-            .raw_color()
-            .component_mul(&((scene.ambient_color / 255.) * scene.ambient_coeff));
-
-        // Trace shadow rays
-        for light in scene.lights.iter() {
-            let pos = hit.pos + hit.normal * f32::EPSILON.sqrt();
-            let dir = *light.pos() - pos;
-            let dist = dir.norm();
-            let shadow_ray = Ray::new(pos, dir);
-            if let Some((_, shadow_hit)) = scene.intersect(&shadow_ray) {
-                if shadow_hit.dist > dist {
-                    // Diffuse/specular color
-                    color += material
-                        .color(&shadow_ray, &ray, &hit)
-                        .component_mul(&((*light.color() / 255.) * light.intensity()));
+}
+
+// Keeps adding samples to a pixel until the estimated error of its brightness is below
+// `threshold` times the brightness, or it has `max_samples` samples. The brightness is clamped
+// to one 8-bit level so that dark pixels don't need unreasonable numbers of samples.
+#[derive(Clone, Copy, Debug)]
+pub struct AdaptiveSampling {
+    pub threshold: f32,
+    // 16 times the settings' `samples` when not given
+    pub max_samples: Option<u32>,
+}
+
+pub struct Render {
+    pub framebuffer: Framebuffer,
+    // Number of samples taken for each pixel, row by row
+    pub sample_counts: Vec<u32>,
+    max_samples: u32,
+}
+
+impl Render {
+    // Colors every pixel by the number of samples it took, from blue for none to red for the
+    // maximum
+    pub fn heatmap(&self) -> RgbImage {
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        RgbImage::from_fn(width, height, |x, y| {
+            let count = self.sample_counts[(y * width + x) as usize];
+            heatmap_color(count as f32 / self.max_samples as f32)
+        })
+    }
+}
+
+// Every pixel is computed independently so the result does not depend on the number of threads
+pub fn ray_trace(scene: &Scene, settings: &RenderSettings) -> Render {
+    let mut tiles = Vec::new();
+    for y in (0..settings.height).step_by(TILE_SIZE as usize) {
+        for x in (0..settings.width).step_by(TILE_SIZE as usize) {
+            tiles.push((x, y));
+        }
+    }
+
+    let filter = FilterSampler::new(&settings.filter);
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(settings.threads)
+        .build()
+        .unwrap();
+    let rendered: Vec<_> = pool.install(|| {
+        tiles
+            .par_iter()
+            .map(|&(x, y)| render_tile(scene, settings, &filter, x, y))
+            .collect()
+    });
+
+    let mut framebuffer = Framebuffer::new(settings.width, settings.height);
+    let mut sample_counts = vec![0; (settings.width * settings.height) as usize];
+    for ((tile_x, tile_y), tile) in tiles.into_iter().zip(rendered) {
+        for (x, y, color, count) in tile {
+            let (x, y) = (tile_x + x, tile_y + y);
+            framebuffer.put(x, y, color);
+            sample_counts[(y * settings.width + x) as usize] = count;
+        }
+    }
+    Render {
+        framebuffer,
+        sample_counts,
+        max_samples: settings.max_samples(),
+    }
+}
+
+// Renders the tile with its top left corner at (`tile_x`, `tile_y`), returning the pixels with
+// their coordinates relative to that corner and the number of samples they took
+fn render_tile(
+    scene: &Scene,
+    settings: &RenderSettings,
+    filter: &FilterSampler,
+    tile_x: u32,
+    tile_y: u32,
+) -> Vec<(u32, u32, Vec3, u32)> {
+    let mut sampler = settings
+        .sampler
+        .new_sampler(settings.max_samples(), settings.seed);
+    let mut pixels = Vec::new();
+    for y in tile_y..u32::min(tile_y + TILE_SIZE, settings.height) {
+        for x in tile_x..u32::min(tile_x + TILE_SIZE, settings.width) {
+            let (color, count) = render_pixel(scene, settings, filter, sampler.as_mut(), x, y);
+            // Colors are computed on the same 0 to 255 scale as the scene description
+            pixels.push((x - tile_x, y - tile_y, color / 255., count));
+        }
+    }
+    pixels
+}
+
+// Returns the filtered color of the pixel and the number of samples taken
+fn render_pixel(
+    scene: &Scene,
+    settings: &RenderSettings,
+    filter: &FilterSampler,
+    sampler: &mut dyn Sampler,
+    x: u32,
+    y: u32,
+) -> (Vec3, u32) {
+    let (width, height) = (settings.width, settings.height);
+    let aspect_ratio = width as f32 / height as f32;
+    let max_samples = settings.max_samples();
+
+    let mut color = Vec3::zeros();
+    let mut weight_sum = 0.;
+    let mut abs_weight_sum = 0.;
+    // Sums of the brightness of the samples and its square, to estimate the variance
+    let mut sum = 0.;
+    let mut sum_squares = 0.;
+    let mut count = 0;
+    while count < max_samples {
+        sampler.start_sample(x, y, count);
+        let (u, v) = sampler.next_2d();
+        // A single sample goes through the pixel center
+        let (u, v) = if max_samples == 1 { (0.5, 0.5) } else { (u, v) };
+        // Offset from the pixel center within the filter's support
+        let (dx, weight_x) = filter.sample(u);
+        let (dy, weight_y) = filter.sample(v);
+        let weight = weight_x * weight_y;
+
+        let lens_sample = sampler.next_2d();
+        let sample_color = match scene.camera.get_ray(
+            x as f32 + 0.5 + dx,
+            y as f32 + 0.5 + dy,
+            width,
+            height,
+            aspect_ratio,
+            lens_sample,
+        ) {
+            Some(ray) => match settings.integrator {
+                Integrator::Whitted => trace_ray(scene, &ray, 0, settings.max_depth, sampler),
+                Integrator::Path => trace_path(scene, &ray, settings.path_depth, sampler),
+            },
+            None => Vec3::zeros(),
+        };
+        color += sample_color * weight;
+        weight_sum += weight;
+        abs_weight_sum += weight.abs();
+
+        let brightness = luminance(&sample_color);
+        sum += brightness;
+        sum_squares += brightness * brightness;
+        count += 1;
+
+        // Check the noise after each batch of `samples` samples
+        if let Some(adaptive) = settings.adaptive {
+            if count.is_multiple_of(settings.samples) && count > 1 {
+                let n = count as f32;
+                let mean = sum / n;
+                let variance = ((sum_squares - sum * sum / n) / (n - 1.)).max(0.);
+                let error = (variance / n).sqrt();
+                if error <= adaptive.threshold * mean.max(1.) {
+                    break;
                 }
-            } else {
-                // Diffuse/specular color
-                color += material
-                    .color(&shadow_ray, &ray, &hit)
-                    .component_mul(&((*light.color() / 255.) * light.intensity()));
             }
         }
+    }
+    // Negative filter lobes can make the sum of the weights arbitrarily small, which would blow up
+    // the pixel, so it's kept at least half the sum of their magnitudes. With the samples
+    // distributed by the magnitude of the filter, that rarely happens unless there are few samples.
+    (color / weight_sum.max(0.5 * abs_weight_sum), count)
+}
 
-        if depth >= max_depth {
-            return color;
+// Maps `t` in [0, 1] to a color going from blue through cyan, green and yellow to red
+fn heatmap_color(t: f32) -> Rgb<u8> {
+    const STOPS: [[f32; 3]; 5] = [
+        [0., 0., 255.],
+        [0., 255., 255.],
+        [0., 255., 0.],
+        [255., 255., 0.],
+        [255., 0., 0.],
+    ];
+    let t = clamp(t, 0., 1.) * (STOPS.len() - 1) as f32;
+    let i = (t as usize).min(STOPS.len() - 2);
+    let f = t - i as f32;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    Rgb::from_channels(
+        (a[0] + (b[0] - a[0]) * f) as u8,
+        (a[1] + (b[1] - a[1]) * f) as u8,
+        (a[2] + (b[2] - a[2]) * f) as u8,
+        255,
+    )
+}
+
+fn trace_ray(
+    scene: &Scene,
+    ray: &Ray,
+    depth: u16,
+    max_depth: u16,
+    sampler: &mut dyn Sampler,
+) -> Vec3 {
+    let (i, hit) = match scene.intersect(ray) {
+        Some(result) => result,
+        None => return scene.background.color(&ray.dir),
+    };
+    let material = scene.objects[i].material();
+
+    // Ambient color
+    let mut color = material
+        .raw_color()
+        .component_mul(&((scene.ambient_color / 255.) * scene.ambient_coeff));
+
+    color += direct_light(scene, ray, &hit, material, sampler);
+
+    // Light that passes through the surface isn't scattered by it
+    let transparency = material.transparency();
+    color *= 1. - transparency;
+
+    color += material.emitted();
+
+    if depth >= max_depth {
+        return color;
+    }
+
+    // Get reflected color
+    let reflectivity = material.reflectivity();
+    if reflectivity > 0. {
+        let reflected_ray = reflected_ray(ray, &hit);
+        let reflected_color = trace_ray(scene, &reflected_ray, depth + 1, max_depth, sampler);
+        color += reflected_color * reflectivity;
+    }
+
+    // Get refracted color, split with the reflected color by the Fresnel reflectance
+    if transparency > 0. {
+        let (refracted_color, reflectance) = match refracted_ray(ray, &hit, material.ior()) {
+            Some((refracted_ray, reflectance)) => (
+                trace_ray(scene, &refracted_ray, depth + 1, max_depth, sampler),
+                reflectance,
+            ),
+            // Total internal reflection
+            None => (Vec3::new(0., 0., 0.), 1.),
+        };
+        let reflected_ray = reflected_ray(ray, &hit);
+        let reflected_color = trace_ray(scene, &reflected_ray, depth + 1, max_depth, sampler);
+        color +=
+            (reflected_color * reflectance + refracted_color * (1. - reflectance)) * transparency;
+    }
+    color
+}
+
+// Diffuse and specular color from the lights that are visible from the hit
+fn direct_light(
+    scene: &Scene,
+    ray: &Ray,
+    hit: &Intersection,
+    material: &Material,
+    sampler: &mut dyn Sampler,
+) -> Vec3 {
+    let mut color = Vec3::new(0., 0., 0.);
+
+    // Trace shadow rays. Lights with an area are split into one point light per sample, so the
+    // fraction of the samples that are visible gives the soft shadows.
+    let pos = hit.pos + hit.normal * f32::EPSILON.sqrt();
+    for light in scene.lights.iter() {
+        let samples = light.samples();
+        for _ in 0..samples {
+            // Lights without an area ignore the sample
+            let (u1, u2) = sampler.next_2d();
+            let sample = light.sample(&pos, u1, u2);
+            if let Some(shadow_ray) = shadow_ray(scene, &pos, &sample.dir, sample.dist) {
+                let attenuation = light.attenuation().factor(sample.dist);
+                color += material
+                    .color(&shadow_ray, ray, hit)
+                    .component_mul(&(sample.radiance * attenuation / samples as f32));
+            }
         }
+    }
+    color
+}
+
+// Returns the ray from `pos` in direction `dir` if nothing is hit within `dist`
+fn shadow_ray(scene: &Scene, pos: &Vec3, dir: &Vec3, dist: f32) -> Option<Ray> {
+    let shadow_ray = Ray::new(*pos, *dir);
+    match scene.intersect(&shadow_ray) {
+        // Emissive surfaces are sampled on the surface itself, which must not shadow the sample
+        Some((_, shadow_hit)) if shadow_hit.dist <= dist - f32::EPSILON.sqrt() => None,
+        _ => Some(shadow_ray),
+    }
+}
 
-        // Get reflected color
+// Follows a single random path from the camera, adding the direct light at every bounce (next
+// event estimation) and picking one of the diffuse, mirror and transmission lobes of the
+// material to continue the path
+fn trace_path(scene: &Scene, ray: &Ray, max_depth: u32, sampler: &mut dyn Sampler) -> Vec3 {
+    let mut color = Vec3::new(0., 0., 0.);
+    let mut throughput = Vec3::new(1., 1., 1.);
+    let mut ray = ray.clone();
+    // Emission of surfaces that are sampled as lights is already added by the direct lighting at
+    // the previous hit, unless that hit was a mirror or transmission which can't sample lights
+    let mut specular_bounce = true;
+    for depth in 0..max_depth {
+        let (i, hit) = match scene.intersect(&ray) {
+            Some(result) => result,
+            None => {
+                // The background lights the scene through the bounces that escape
+                color += throughput.component_mul(&scene.background.color(&ray.dir));
+                break;
+            }
+        };
+        let material = scene.objects[i].material();
+        let transparency = material.transparency();
         let reflectivity = material.reflectivity();
-        if reflectivity > 0. {
-            let reflected_ray = reflected_ray(ray, &hit);
-            let reflected_color = trace_ray(scene, &reflected_ray, depth + 1, max_depth);
-            color += reflected_color * reflectivity;
+
+        if specular_bounce || !scene.sampled_emitters[i] {
+            color += throughput.component_mul(&material.emitted());
+        }
+
+        color += throughput.component_mul(&direct_light(scene, &ray, &hit, material, sampler))
+            * (1. - transparency);
+
+        let albedo = material.albedo(&hit) * (1. - transparency);
+        let diffuse_weight = albedo.max();
+        let total_weight = diffuse_weight + reflectivity + transparency;
+        if total_weight <= 0. {
+            break;
+        }
+
+        // Each lobe is picked with probability proportional to its weight, so the throughput is
+        // scaled by the weight divided by that probability
+        let choice = sampler.next_1d() * total_weight;
+        let (next_ray, weight) = if choice < diffuse_weight {
+            let normal = facing_normal(&ray, &hit);
+            let pos = hit.pos + normal * f32::EPSILON.sqrt();
+            let (u1, u2) = sampler.next_2d();
+            let dir = cosine_sample_hemisphere(&normal, u1, u2);
+            specular_bounce = false;
+            (Ray::new(pos, dir), albedo * total_weight / diffuse_weight)
+        } else if choice < diffuse_weight + reflectivity {
+            specular_bounce = true;
+            (reflected_ray(&ray, &hit), Vec3::repeat(total_weight))
+        } else {
+            specular_bounce = true;
+            match refracted_ray(&ray, &hit, material.ior()) {
+                Some((refracted_ray, reflectance)) if sampler.next_1d() >= reflectance => {
+                    (refracted_ray, Vec3::repeat(total_weight))
+                }
+                _ => (reflected_ray(&ray, &hit), Vec3::repeat(total_weight)),
+            }
+        };
+        throughput = throughput.component_mul(&weight);
+        ray = next_ray;
+
+        if depth >= ROULETTE_DEPTH {
+            let survival = throughput.max().min(0.95);
+            if sampler.next_1d() >= survival {
+                break;
+            }
+            throughput /= survival;
         }
     }
     color
 }
 
+// Cosine weighted direction in the hemisphere around `normal` from two uniform samples
+fn cosine_sample_hemisphere(normal: &Vec3, u1: f32, u2: f32) -> Vec3 {
+    let r = u1.sqrt();
+    let phi = 2. * f32::consts::PI * u2;
+    let z = (1. - u1).max(0.).sqrt();
+
+    let axis = if normal.x.abs() > 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let tangent = normal.cross(&axis).normalize();
+    let bitangent = normal.cross(&tangent);
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * z
+}
+
+// Returns the surface normal on the side the ray is coming from
+fn facing_normal(ray: &Ray, hit: &Intersection) -> Vec3 {
+    if ray.dir.dot(&hit.normal) > 0. {
+        -hit.normal
+    } else {
+        hit.normal
+    }
+}
+
 fn reflected_ray(ray: &Ray, hit: &Intersection) -> Ray {
-    let pos = hit.pos + hit.normal * f32::EPSILON.sqrt();
-    let dir = ray.dir - hit.normal * 2. * ray.dir.dot(&hit.normal);
+    let normal = facing_normal(ray, hit);
+    let pos = hit.pos + normal * f32::EPSILON.sqrt();
+    let dir = ray.dir - normal * 2. * ray.dir.dot(&normal);
     Ray::new(pos, dir)
 }
+
+// Refracts the ray through a surface separating air from a medium with index of refraction
+// `ior`, returning the refracted ray and the Fresnel reflectance (Schlick's approximation), or
+// `None` on total internal reflection
+fn refracted_ray(ray: &Ray, hit: &Intersection, ior: f32) -> Option<(Ray, f32)> {
+    let normal = facing_normal(ray, hit);
+    let entering = ray.dir.dot(&hit.normal) <= 0.;
+    let (n1, n2) = if entering { (1., ior) } else { (ior, 1.) };
+    let eta = n1 / n2;
+
+    let cos_i = -ray.dir.dot(&normal);
+    let sin2_t = eta * eta * (1. - cos_i * cos_i);
+    if sin2_t > 1. {
+        return None;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    // Use the angle on the less dense side of the surface
+    let cos = if n1 <= n2 { cos_i } else { cos_t };
+    let reflectance = r0 + (1. - r0) * (1. - cos).powi(5);
+
+    let pos = hit.pos - normal * f32::EPSILON.sqrt();
+    let dir = ray.dir * eta + normal * (eta * cos_i - cos_t);
+    Some((Ray::new(pos, dir), reflectance))
+}
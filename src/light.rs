@@ -1,17 +1,95 @@
+use std::f32;
+
 use crate::Vec3;
 
+use nalgebra::{Matrix3, Matrix4, Point3, U3};
+
+pub struct LightSample {
+    // Unit vector from the shaded point towards the light
+    pub dir: Vec3,
+    // Distance to the sampled point on the light, infinite for directional lights
+    pub dist: f32,
+    // Light arriving at the shaded point, colors are in the 0-255 range scaled by the intensity
+    pub radiance: Vec3,
+}
+
+// Falloff of the light with distance, the radiance is divided by
+// `constant + linear * dist + quadratic * dist^2`
+#[derive(Clone, Copy, Debug)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Attenuation {
+            constant,
+            linear,
+            quadratic,
+        }
+    }
+
+    pub fn none() -> Self {
+        Attenuation::new(1., 0., 0.)
+    }
+
+    pub fn linear() -> Self {
+        Attenuation::new(0., 1., 0.)
+    }
+
+    pub fn inverse_square() -> Self {
+        Attenuation::new(0., 0., 1.)
+    }
+
+    pub fn factor(&self, dist: f32) -> f32 {
+        // Avoid 0 * inf for lights at infinity
+        if self.linear == 0. && self.quadratic == 0. {
+            return 1. / self.constant;
+        }
+        1. / (self.constant + self.linear * dist + self.quadratic * dist * dist)
+    }
+}
+
+pub trait Light: Send + Sync {
+    // Samples the light as seen from `point`, using two uniform samples for lights with an area
+    fn sample(&self, point: &Vec3, u1: f32, u2: f32) -> LightSample;
+    fn attenuation(&self) -> Attenuation {
+        Attenuation::none()
+    }
+    // Number of shadow rays traced towards the light per hit, the radiance of the samples is
+    // averaged
+    fn samples(&self) -> u32 {
+        1
+    }
+}
+
+// Sample towards `light_pos`, which receives `radiance` at any distance
+fn sample_towards(point: &Vec3, light_pos: &Vec3, radiance: Vec3) -> LightSample {
+    let dir = light_pos - point;
+    let dist = dir.norm();
+    LightSample {
+        dir: dir / dist,
+        dist,
+        radiance,
+    }
+}
+
 pub struct PointLight {
     pos: Vec3,
     color: Vec3,
     intensity: f32,
+    attenuation: Attenuation,
 }
 
 impl PointLight {
-    pub fn new(pos: Vec3, color: Vec3, intensity: f32) -> Self {
+    pub fn new(pos: Vec3, color: Vec3, intensity: f32, attenuation: Attenuation) -> Self {
         PointLight {
             pos,
             color,
             intensity,
+            attenuation,
         }
     }
 
@@ -27,3 +105,350 @@ impl PointLight {
         self.intensity
     }
 }
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vec3, _u1: f32, _u2: f32) -> LightSample {
+        sample_towards(point, &self.pos, self.color / 255. * self.intensity)
+    }
+
+    fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+}
+
+pub enum AreaShape {
+    // Rectangle centered at `center` spanned by the edge vectors `u` and `v`
+    Rect {
+        center: Vec3,
+        u: Vec3,
+        v: Vec3,
+    },
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    // A sphere around the origin stretched by the linear transformation `linear`, whose inverse
+    // is `inverse`, and moved to `center`
+    Ellipsoid {
+        center: Vec3,
+        radius: f32,
+        linear: Matrix3<f32>,
+        inverse: Matrix3<f32>,
+    },
+    // Any number of triangles, `cdf` is the cumulative distribution of their areas used to pick
+    // one in proportion to its area and `area` is their total area
+    Triangles {
+        triangles: Vec<[Vec3; 3]>,
+        cdf: Vec<f32>,
+        area: f32,
+    },
+}
+
+impl AreaShape {
+    // `None` if the triangles have no area to sample, like a mesh without faces
+    pub fn triangles(triangles: Vec<[Vec3; 3]>) -> Option<Self> {
+        let mut cdf = Vec::with_capacity(triangles.len());
+        let mut total = 0.;
+        for [a, b, c] in triangles.iter() {
+            total += (b - a).cross(&(c - a)).norm() / 2.;
+            cdf.push(total);
+        }
+        if total <= 0. {
+            return None;
+        }
+        for c in cdf.iter_mut() {
+            *c /= total;
+        }
+        Some(AreaShape::Triangles {
+            triangles,
+            cdf,
+            area: total,
+        })
+    }
+
+    // The shape moved by the affine transformation `m`. A sphere only stays a sphere if `m`
+    // scales it uniformly, otherwise it becomes an ellipsoid. `None` if `m` flattens the shape to
+    // nothing.
+    pub fn transformed(&self, m: &Matrix4<f32>) -> Option<Self> {
+        let point = |p: &Vec3| m.transform_point(&Point3::from(*p)).coords;
+        let linear: Matrix3<f32> = m.fixed_slice::<U3, U3>(0, 0).into_owned();
+        let ellipsoid = |center: &Vec3, radius: f32, linear: Matrix3<f32>| {
+            Some(AreaShape::Ellipsoid {
+                center: point(center),
+                radius,
+                linear,
+                inverse: linear.try_inverse()?,
+            })
+        };
+        match self {
+            AreaShape::Rect { center, u, v } => Some(AreaShape::Rect {
+                center: point(center),
+                u: m.transform_vector(u),
+                v: m.transform_vector(v),
+            }),
+            AreaShape::Sphere { center, radius } => {
+                let scale = linear.column(0).norm();
+                let uniform = (linear.transpose() * linear - Matrix3::identity() * scale * scale)
+                    .iter()
+                    .all(|e| e.abs() <= 1e-4 * scale * scale);
+                if !uniform {
+                    return ellipsoid(center, *radius, linear);
+                }
+                Some(AreaShape::Sphere {
+                    center: point(center),
+                    radius: radius * scale,
+                })
+            }
+            AreaShape::Ellipsoid {
+                center,
+                radius,
+                linear: ellipsoid_linear,
+                ..
+            } => ellipsoid(center, *radius, linear * ellipsoid_linear),
+            AreaShape::Triangles { triangles, .. } => AreaShape::triangles(
+                triangles
+                    .iter()
+                    .map(|[a, b, c]| [point(a), point(b), point(c)])
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub struct AreaLight {
+    shape: AreaShape,
+    color: Vec3,
+    intensity: f32,
+    samples: u32,
+    attenuation: Attenuation,
+    // Whether the color is the radiance leaving every point of the shape, as for an emissive
+    // surface, rather than the light of a point light spread over the shape
+    emissive: bool,
+}
+
+impl AreaLight {
+    pub fn new(
+        shape: AreaShape,
+        color: Vec3,
+        intensity: f32,
+        samples: u32,
+        attenuation: Attenuation,
+    ) -> Self {
+        AreaLight {
+            shape,
+            color,
+            intensity,
+            samples,
+            attenuation,
+            emissive: false,
+        }
+    }
+
+    // The light given off by an emissive surface covering `shape`, which gets dimmer with the
+    // distance and towards the edges of the shape by itself
+    pub fn emissive(shape: AreaShape, color: Vec3, intensity: f32, samples: u32) -> Self {
+        AreaLight {
+            emissive: true,
+            ..AreaLight::new(shape, color, intensity, samples, Attenuation::none())
+        }
+    }
+
+    // The same light with its shape moved by `m`, see `AreaShape::transformed`
+    pub fn transformed(self, m: &Matrix4<f32>) -> Option<Self> {
+        Some(AreaLight {
+            shape: self.shape.transformed(m)?,
+            ..self
+        })
+    }
+
+    // Picks a point on the light from two uniform samples, and returns it with the normal there
+    // and the area the point was picked from. For spheres and ellipsoids only the half facing
+    // `from` is sampled.
+    fn sample_point(&self, from: &Vec3, u1: f32, u2: f32) -> (Vec3, Vec3, f32) {
+        match &self.shape {
+            AreaShape::Rect { center, u, v } => {
+                let normal = u.cross(v);
+                let point = center + u * (u1 - 0.5) + v * (u2 - 0.5);
+                (point, normal.normalize(), normal.norm())
+            }
+            AreaShape::Sphere { center, radius } => {
+                let dir = sample_hemisphere(&(from - center), u1, u2);
+                let area = 2. * f32::consts::PI * radius * radius;
+                (center + dir * *radius, dir, area)
+            }
+            AreaShape::Ellipsoid {
+                center,
+                radius,
+                linear,
+                inverse,
+            } => {
+                // Sample the sphere before it was stretched, the stretching scales the area
+                // around the point by the determinant times the length of the transformed normal
+                let dir = sample_hemisphere(&(inverse * (from - center)), u1, u2);
+                let normal = inverse.transpose() * dir;
+                let area = 2. * f32::consts::PI * radius * radius;
+                let area = area * linear.determinant().abs() * normal.norm();
+                (center + linear * dir * *radius, normal.normalize(), area)
+            }
+            AreaShape::Triangles {
+                triangles,
+                cdf,
+                area,
+            } => {
+                // Pick a triangle with u1 and reuse what's left of it to pick the point
+                let i = cdf.partition_point(|&c| c <= u1).min(triangles.len() - 1);
+                let start = if i == 0 { 0. } else { cdf[i - 1] };
+                let u1 = ((u1 - start) / (cdf[i] - start)).clamp(0., 1.);
+
+                let [a, b, c] = triangles[i];
+                let r = u1.sqrt();
+                let point = a * (1. - r) + b * (r * (1. - u2)) + c * (r * u2);
+                (point, (b - a).cross(&(c - a)).normalize(), *area)
+            }
+        }
+    }
+
+    pub fn color(&self) -> &Vec3 {
+        &self.color
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+}
+
+// Uniformly distributed unit vector in the hemisphere around `towards`, from two uniform samples
+fn sample_hemisphere(towards: &Vec3, u1: f32, u2: f32) -> Vec3 {
+    let z = 1. - 2. * u1;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * f32::consts::PI * u2;
+    let dir = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+    if dir.dot(towards) < 0. {
+        -dir
+    } else {
+        dir
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, point: &Vec3, u1: f32, u2: f32) -> LightSample {
+        let (light_pos, normal, area) = self.sample_point(point, u1, u2);
+        let radiance = self.color / 255. * self.intensity;
+        if !self.emissive {
+            return sample_towards(point, &light_pos, radiance);
+        }
+
+        // The sample stands for the whole area it was picked from, as seen from `point`. Both
+        // sides of the surface emit, the far side of a sphere is in its own shadow. The light is
+        // divided by pi to match diffuse surfaces lit by the emission through path tracing.
+        let to_point = point - light_pos;
+        let dist_squared = to_point.norm_squared();
+        let cos_light = normal.dot(&to_point).abs() / dist_squared.sqrt();
+        let scale = area * cos_light / (f32::consts::PI * dist_squared);
+        sample_towards(point, &light_pos, radiance * scale)
+    }
+
+    fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+}
+
+// A light infinitely far away, such as the sun, shining in the same direction everywhere
+pub struct DirectionalLight {
+    dir: Vec3,
+    color: Vec3,
+    intensity: f32,
+}
+
+impl DirectionalLight {
+    pub fn new(dir: Vec3, color: Vec3, intensity: f32) -> Self {
+        DirectionalLight {
+            dir: dir.normalize(),
+            color,
+            intensity,
+        }
+    }
+
+    // Direction the light travels in
+    pub fn dir(&self) -> &Vec3 {
+        &self.dir
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vec3, _u1: f32, _u2: f32) -> LightSample {
+        LightSample {
+            dir: -self.dir,
+            dist: f32::INFINITY,
+            radiance: self.color / 255. * self.intensity,
+        }
+    }
+}
+
+// A point light restricted to a cone. The light is at full intensity within `inner_angle` of
+// the axis and fades out to nothing at `outer_angle`, with `falloff` shaping the transition.
+pub struct SpotLight {
+    pos: Vec3,
+    dir: Vec3,
+    color: Vec3,
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    falloff: f32,
+    attenuation: Attenuation,
+}
+
+impl SpotLight {
+    // Angles are in degrees, measured from the axis of the cone
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pos: Vec3,
+        dir: Vec3,
+        color: Vec3,
+        intensity: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+        falloff: f32,
+        attenuation: Attenuation,
+    ) -> Self {
+        SpotLight {
+            pos,
+            dir: dir.normalize(),
+            color,
+            intensity,
+            cos_inner: inner_angle.to_radians().cos(),
+            cos_outer: outer_angle.to_radians().cos(),
+            falloff,
+            attenuation,
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vec3, _u1: f32, _u2: f32) -> LightSample {
+        let mut sample = sample_towards(point, &self.pos, self.color / 255. * self.intensity);
+        let cos_angle = -sample.dir.dot(&self.dir);
+        let t = if self.cos_inner > self.cos_outer {
+            (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer)
+        } else if cos_angle >= self.cos_outer {
+            1.
+        } else {
+            0.
+        };
+        // Outside the outer cone, even with no falloff (0^0 is 1)
+        if t <= 0. {
+            sample.radiance = Vec3::zeros();
+        } else {
+            sample.radiance *= t.min(1.).powf(self.falloff);
+        }
+        sample
+    }
+
+    fn attenuation(&self) -> Attenuation {
+        self.attenuation
+    }
+}
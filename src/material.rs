@@ -12,6 +12,11 @@ pub struct Material {
     specular_coeff: f32,
     glossiness: f32,
     reflectivity: f32,
+    transparency: f32,
+    ior: f32,
+    // Light given off by the surface, on the same 0 to 255 scale as `color`
+    emission: Vec3,
+    emission_strength: f32,
     texture: Option<Box<dyn Texture>>,
     normal_map: Option<NormalMap>,
     displacement_map: Option<DisplacementMap>,
@@ -25,6 +30,10 @@ impl Clone for Material {
             specular_coeff: self.specular_coeff,
             glossiness: self.glossiness,
             reflectivity: self.reflectivity,
+            transparency: self.transparency,
+            ior: self.ior,
+            emission: self.emission,
+            emission_strength: self.emission_strength,
             texture: self.texture.as_ref().map(|t| t.clone_()),
             normal_map: self.normal_map.as_ref().cloned(),
             displacement_map: self.displacement_map.as_ref().cloned(),
@@ -33,12 +42,17 @@ impl Clone for Material {
 }
 
 impl Material {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         color: Vec3,
         diffuse_coeff: f32,
         specular_coeff: f32,
         glossiness: f32,
         reflectivity: f32,
+        transparency: f32,
+        ior: f32,
+        emission: Vec3,
+        emission_strength: f32,
         texture: Option<Box<dyn Texture>>,
         normal_map: Option<NormalMap>,
         displacement_map: Option<DisplacementMap>,
@@ -49,6 +63,10 @@ impl Material {
             specular_coeff,
             glossiness,
             reflectivity,
+            transparency,
+            ior,
+            emission,
+            emission_strength,
             texture,
             normal_map,
             displacement_map,
@@ -59,17 +77,45 @@ impl Material {
         self.reflectivity
     }
 
+    pub fn transparency(&self) -> f32 {
+        self.transparency
+    }
+
+    // Index of refraction
+    pub fn ior(&self) -> f32 {
+        self.ior
+    }
+
+    pub fn emission(&self) -> &Vec3 {
+        &self.emission
+    }
+
+    pub fn emission_strength(&self) -> f32 {
+        self.emission_strength
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission_strength > 0. && self.emission.max() > 0.
+    }
+
+    // Color of the light given off by the surface
+    pub fn emitted(&self) -> Vec3 {
+        self.emission * self.emission_strength
+    }
+
     pub fn raw_color(&self) -> Vec3 {
         self.color
     }
 
+    // Fraction of the incoming light that is diffusely reflected at the hit
+    pub fn albedo(&self, hit: &Intersection) -> Vec3 {
+        (self.color / 255. * self.diffuse_coeff).component_mul(&self.texture_color(hit))
+    }
+
     pub fn color(&self, shadow_ray: &Ray, camera_ray: &Ray, hit: &Intersection) -> Vec3 {
         let f = f32::max(0., hit.normal.dot(&shadow_ray.dir));
         let diffuse_color =
-            (self.color * f * self.diffuse_coeff).component_mul(&match self.texture {
-                Some(ref t) => t.color(hit.u, hit.v) / 255.,
-                None => Vec3::new(1., 1., 1.),
-            });
+            (self.color * f * self.diffuse_coeff).component_mul(&self.texture_color(hit));
 
         // Average the angles, flipping the camera ray because it's in the opposite direction
         let half_vec = ((shadow_ray.dir - camera_ray.dir) / 2.).normalize();
@@ -80,6 +126,13 @@ impl Material {
         diffuse_color + specular_color
     }
 
+    fn texture_color(&self, hit: &Intersection) -> Vec3 {
+        match self.texture {
+            Some(ref t) => t.color(hit.u, hit.v) / 255.,
+            None => Vec3::new(1., 1., 1.),
+        }
+    }
+
     pub fn has_normal_map(&self) -> bool {
         self.normal_map.is_some()
     }
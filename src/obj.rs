@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use crate::Vec3;
+
+pub struct Face {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+}
+
+pub struct Obj {
+    pub vertices: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<Face>,
+}
+
+// Loads the geometry from a Wavefront OBJ file. Only vertices, vertex normals and faces are
+// read, polygons with more than three vertices are triangulated as a fan.
+pub fn load(filename: &str) -> io::Result<Obj> {
+    parse(BufReader::new(File::open(filename)?), filename)
+}
+
+// Reads an OBJ file from `reader`, `filename` is only used in error messages
+fn parse(reader: impl BufRead, filename: &str) -> io::Result<Obj> {
+    let mut obj = Obj {
+        vertices: Vec::new(),
+        normals: Vec::new(),
+        faces: Vec::new(),
+    };
+    for (line_num, line) in reader.lines().enumerate() {
+        let line = line?;
+        let mut tokens = line.split_whitespace();
+        let result = match tokens.next() {
+            Some("v") => parse_vec3(tokens).map(|v| obj.vertices.push(v)),
+            Some("vn") => parse_vec3(tokens).map(|n| obj.normals.push(n.normalize())),
+            Some("f") => parse_face(tokens, &obj).map(|faces| obj.faces.extend(faces)),
+            _ => Ok(()),
+        };
+        result.map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", filename, line_num + 1, e),
+            )
+        })?;
+    }
+    Ok(obj)
+}
+
+fn parse_vec3<'a>(tokens: impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    let v = tokens
+        .take(3)
+        .map(|t| t.parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if v.len() != 3 {
+        return Err("expected three coordinates".to_owned());
+    }
+    Ok(Vec3::new(v[0], v[1], v[2]))
+}
+
+fn parse_face<'a>(tokens: impl Iterator<Item = &'a str>, obj: &Obj) -> Result<Vec<Face>, String> {
+    let mut vertices = Vec::new();
+    let mut normals = Vec::new();
+    for token in tokens {
+        // Each vertex is one of v, v/vt, v//vn or v/vt/vn
+        let mut indices = token.split('/');
+        let v = indices.next().unwrap_or("");
+        vertices.push(parse_index(v, obj.vertices.len())?);
+        match indices.nth(1) {
+            Some(n) if !n.is_empty() => normals.push(parse_index(n, obj.normals.len())?),
+            _ => (),
+        }
+    }
+
+    if vertices.len() < 3 {
+        return Err("face has fewer than three vertices".to_owned());
+    }
+    let has_normals = normals.len() == vertices.len();
+
+    let mut faces = Vec::new();
+    for i in 1..vertices.len() - 1 {
+        faces.push(Face {
+            vertices: [vertices[0], vertices[i], vertices[i + 1]],
+            normals: if has_normals {
+                Some([normals[0], normals[i], normals[i + 1]])
+            } else {
+                None
+            },
+        });
+    }
+    Ok(faces)
+}
+
+// OBJ indices are 1-based, negative indices are relative to the end of the list read so far
+fn parse_index(s: &str, len: usize) -> Result<usize, String> {
+    let i = s.parse::<i64>().map_err(|e| e.to_string())?;
+    let index = if i < 0 { len as i64 + i } else { i - 1 };
+    if index < 0 || index >= len as i64 {
+        return Err(format!("index {} out of range", i));
+    }
+    Ok(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(s: &str) -> io::Result<Obj> {
+        parse(s.as_bytes(), "test.obj")
+    }
+
+    #[test]
+    fn polygons_are_triangulated_as_a_fan() {
+        let obj = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0.5 2 0\nv 0 1 0\n\
+             vn 0 0 2\n\
+             f 1//1 2//1 3//1 4//1 5//1\n",
+        )
+        .unwrap();
+        let faces: Vec<_> = obj.faces.iter().map(|f| f.vertices).collect();
+        assert_eq!(faces, [[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert!(obj.faces.iter().all(|f| f.normals == Some([0; 3])));
+        assert_eq!(obj.normals[0], Vec3::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let obj = parse_str("v 0 0 0\nv 1 0 0\nf 1 2 2\nv 0 1 0\nf -3 -2/1 -1\n").unwrap();
+        assert_eq!(obj.faces[1].vertices, [0, 1, 2]);
+        assert!(obj.faces[1].normals.is_none());
+    }
+
+    #[test]
+    fn bad_indices_report_the_line() {
+        let error = parse_str("v 0 0 0\nv 1 0 0\nf 1 2 3\n").err().unwrap();
+        assert_eq!(error.to_string(), "test.obj:3: index 3 out of range");
+        let error = parse_str("v 0 0 0\nf 1 -2 1\n").err().unwrap();
+        assert_eq!(error.to_string(), "test.obj:2: index -2 out of range");
+    }
+}
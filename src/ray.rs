@@ -1,6 +1,6 @@
 use crate::Vec3;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Ray {
     pub origin: Vec3,
     pub dir: Vec3,
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+
+// Generates the random numbers used for a pixel sample: the position in the pixel, the point on
+// the lens, light samples and the choices made along a path. Each call draws the next dimension
+// of the current sample. The values only depend on the pixel, the sample index and the
+// dimension, so renders are reproducible no matter how the pixels are scheduled.
+pub trait Sampler {
+    // Starts sample `index` of the pixel at (`x`, `y`), going back to the first dimension
+    fn start_sample(&mut self, x: u32, y: u32, index: u32);
+    fn next_1d(&mut self) -> f32;
+    fn next_2d(&mut self) -> (f32, f32);
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplerType {
+    // Uncorrelated random samples
+    Independent,
+    // Jittered samples with one per stratum
+    Stratified,
+    // Randomly shifted Halton sequence
+    Halton,
+    // Owen scrambled Sobol (0, 2)-sequence, padded across dimensions
+    Sobol,
+}
+
+impl SamplerType {
+    pub fn new_sampler(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        let state = SampleState {
+            samples_per_pixel,
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        };
+        match *self {
+            SamplerType::Independent => Box::new(IndependentSampler { state }),
+            SamplerType::Stratified => Box::new(StratifiedSampler { state }),
+            SamplerType::Halton => Box::new(HaltonSampler { state }),
+            SamplerType::Sobol => Box::new(SobolSampler { state }),
+        }
+    }
+}
+
+// Largest f32 below 1
+const ONE_MINUS_EPSILON: f32 = 1. - f32::EPSILON / 2.;
+
+// Bases for the Halton sequence, dimensions past the end of the table use random samples
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+struct SampleState {
+    samples_per_pixel: u32,
+    seed: u64,
+    // Hash of the pixel coordinates and the seed
+    pixel: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // Returns the next dimension and a hash to seed its randomization with
+    fn next_dimension(&mut self) -> (u32, u64) {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        (dimension, hash(&[self.pixel, dimension as u64]))
+    }
+
+    // Random number for the current sample, `salt` distinguishes several numbers drawn for the
+    // same dimension
+    fn random(&self, dimension_hash: u64, salt: u64) -> f32 {
+        to_unit_float(hash(&[dimension_hash, self.index as u64, salt]) as u32)
+    }
+}
+
+struct IndependentSampler {
+    state: SampleState,
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start_sample(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (_, h) = self.state.next_dimension();
+        self.state.random(h, 0)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (_, h) = self.state.next_dimension();
+        (self.state.random(h, 0), self.state.random(h, 1))
+    }
+}
+
+// The samples of a pixel are assigned to strata in a different random order for each dimension,
+// so the dimensions aren't correlated with each other
+struct StratifiedSampler {
+    state: SampleState,
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start_sample(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (_, h) = self.state.next_dimension();
+        let n = self.state.samples_per_pixel;
+        let stratum = permute(self.state.index, n, h as u32);
+        ((stratum as f32 + self.state.random(h, 0)) / n as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (_, h) = self.state.next_dimension();
+        let n = self.state.samples_per_pixel;
+        let (jitter_x, jitter_y) = (self.state.random(h, 0), self.state.random(h, 1));
+
+        let root = (n as f32).sqrt() as u32;
+        let (x, y) = if root * root == n {
+            // Jittered grid
+            let stratum = permute(self.state.index, n, h as u32);
+            (
+                (stratum % root) as f32 + jitter_x,
+                (stratum / root) as f32 + jitter_y,
+            )
+        } else {
+            // Latin hypercube, one sample in each row and column
+            (
+                permute(self.state.index, n, h as u32) as f32 + jitter_x,
+                permute(self.state.index, n, (h >> 32) as u32) as f32 + jitter_y,
+            )
+        };
+        let strata = if root * root == n { root } else { n } as f32;
+        (
+            (x / strata).min(ONE_MINUS_EPSILON),
+            (y / strata).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
+
+// Every pixel shifts the sequence by a random offset per dimension (Cranley-Patterson rotation)
+struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    fn sample(&self, dimension: u32, h: u64) -> f32 {
+        let base = match PRIMES.get(dimension as usize) {
+            Some(&base) => base,
+            None => return self.state.random(h, 0),
+        };
+        let offset = to_unit_float(h as u32);
+        let v = radical_inverse(base, self.state.index) + offset;
+        (v - v.floor()).min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start_sample(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (dimension, h) = self.state.next_dimension();
+        self.sample(dimension, h)
+    }
+
+    // Uses two consecutive dimensions
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+// Each pair of dimensions uses the first two dimensions of the Sobol sequence, with the sample
+// order shuffled per dimension so the pairs aren't correlated. Works best with a power of two
+// samples per pixel.
+struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    fn sample_index(&self, h: u64) -> u32 {
+        permute(self.state.index, self.state.samples_per_pixel, h as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.state.start_sample(x, y, index);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let (_, h) = self.state.next_dimension();
+        let i = self.sample_index(h);
+        to_unit_float(owen_scramble(i.reverse_bits(), (h >> 32) as u32))
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (_, h) = self.state.next_dimension();
+        let i = self.sample_index(h);
+        let seed = hash(&[h, 1]);
+        (
+            to_unit_float(owen_scramble(i.reverse_bits(), seed as u32)),
+            to_unit_float(owen_scramble(
+                sobol_second_dimension(i),
+                (seed >> 32) as u32,
+            )),
+        )
+    }
+}
+
+fn to_unit_float(v: u32) -> f32 {
+    (v as f32 * (1. / 4_294_967_296.)).min(ONE_MINUS_EPSILON)
+}
+
+// Mixes the values into a well distributed 64 bit hash
+fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, &v| {
+        // SplitMix64 finalizer
+        let mut z = (h ^ v).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    })
+}
+
+// Element `i` of a random permutation of 0..n chosen by `seed`, from Kensler's "Correlated
+// Multi-Jittered Sampling"
+fn permute(mut i: u32, n: u32, seed: u32) -> u32 {
+    let p = seed;
+    let mut w = n.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    // In 64 bits, wrapping around 2^32 would map two elements to the same one
+    ((i as u64 + p as u64) % n as u64) as u32
+}
+
+fn radical_inverse(base: u32, mut i: u32) -> f32 {
+    let inv_base = 1. / base as f64;
+    let mut inv_base_n = 1.;
+    let mut reversed = 0u64;
+    while i > 0 {
+        reversed = reversed * base as u64 + (i % base) as u64;
+        inv_base_n *= inv_base;
+        i /= base;
+    }
+    (reversed as f64 * inv_base_n) as f32
+}
+
+// The second dimension of the Sobol sequence as a 32 bit fixed point number, the first is just
+// the bits of the index reversed
+fn sobol_second_dimension(mut i: u32) -> u32 {
+    let mut v = 1 << 31;
+    let mut result = 0;
+    while i > 0 {
+        if i & 1 == 1 {
+            result ^= v;
+        }
+        i >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Hash based approximation of Owen scrambling (Laine and Karras), which randomizes the digits of
+// `v` while keeping the stratification of the sequence
+fn owen_scramble(v: u32, seed: u32) -> u32 {
+    let mut v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permute_is_a_bijection() {
+        for n in [1, 2, 3, 7, 16, 100, 257].iter().copied() {
+            for seed in [0, 1, 0xdead_beef, u32::MAX].iter().copied() {
+                let mut seen = vec![false; n as usize];
+                for i in 0..n {
+                    let j = permute(i, n, seed);
+                    assert!(j < n);
+                    assert!(!seen[j as usize], "{} appears twice for n = {}", j, n);
+                    seen[j as usize] = true;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1041 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::background::{Background, EnvironmentMap};
+use crate::camera::{Camera, Fov, Projection};
+use crate::description::{
+    AreaLightDescription, AreaShapeType, AttenuationDescription, AttenuationType,
+    BackgroundDescription, CameraDescription, ColorBackgroundDescription,
+    DirectionalLightDescription, EnvironmentDescription, FovAxis, GradientBackgroundDescription,
+    LightDescription, MaterialDescription, MeshDescription, PlaneDescription,
+    PointLightDescription, ProjectionType, ScaleDescription, SceneDescription, SkyDescription,
+    SphereDescription, SpotLightDescription, SurfaceDescription, TransformDescription,
+    WorldDescription,
+};
+use crate::filter::Filter;
+use crate::light::{
+    AreaLight, AreaShape, Attenuation, DirectionalLight, Light, PointLight, SpotLight,
+};
+use crate::material::{DisplacementMap, Material, NormalMap};
+use crate::sampler::SamplerType;
+use crate::sky::Sky;
+use crate::surface::{Plane, Sphere, Surface, Transformed, TriangleMesh};
+use crate::texture::{CheckerboardTexture, ImageTexture, Texture};
+use crate::tonemap::{ToneMapper, ToneMapping};
+use crate::{AdaptiveSampling, Integrator, RenderSettings, Scene, Vec3};
+
+use nalgebra::{Matrix4, Rotation3};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+// A problem with a config or scene file, along with the file and the path to the offending
+// value, like `scene.surface[3].radius`
+#[derive(Debug)]
+pub struct SceneError {
+    // `None` when loading from a string
+    pub file: Option<String>,
+    // Empty for problems with the whole file
+    pub path: String,
+    pub kind: SceneErrorKind,
+}
+
+#[derive(Debug)]
+pub enum SceneErrorKind {
+    // The file couldn't be read
+    Io(io::Error),
+    // The file isn't valid TOML, JSON or YAML or doesn't match the schema, like a missing key or a
+    // value of the wrong type
+    Parse(Box<dyn Error + Send + Sync>),
+    // The value has the right type but can't be used, like an unknown material or a texture that
+    // doesn't exist
+    Invalid(String),
+    // A description couldn't be written in the format
+    Serialize(Box<dyn Error + Send + Sync>),
+}
+
+impl SceneError {
+    fn invalid(path: impl Into<String>, message: impl Into<String>) -> Self {
+        SceneError {
+            file: None,
+            path: path.into(),
+            kind: SceneErrorKind::Invalid(message.into()),
+        }
+    }
+
+    fn in_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_owned());
+        self
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        match &self.kind {
+            SceneErrorKind::Io(e) => write!(f, "{}", e),
+            SceneErrorKind::Parse(e) => write!(f, "{}", e),
+            SceneErrorKind::Invalid(message) => write!(f, "{}", message),
+            SceneErrorKind::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            SceneErrorKind::Io(e) => Some(e),
+            SceneErrorKind::Parse(e) => Some(e.as_ref()),
+            SceneErrorKind::Invalid(_) => None,
+            SceneErrorKind::Serialize(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneError>;
+
+// The formats scene files can be written in, all with the same schema
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SceneFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl SceneFormat {
+    // Picks the format from the extension, TOML unless it's .json, .yaml or .yml
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("json") => SceneFormat::Json,
+            Some("yaml") | Some("yml") => SceneFormat::Yaml,
+            _ => SceneFormat::Toml,
+        }
+    }
+}
+
+impl Scene {
+    // Parses a scene in the format of the files in `scenes/`. Paths to meshes and images in the
+    // scene are relative to the working directory.
+    pub fn parse(s: &str, format: SceneFormat) -> Result<Self> {
+        SceneDescription::parse(s, format)?.build(Path::new(""))
+    }
+
+    pub fn from_toml_str(toml_str: &str) -> Result<Self> {
+        Scene::parse(toml_str, SceneFormat::Toml)
+    }
+
+    // The extension of the file picks the format. Paths to meshes and images in the scene are
+    // relative to the directory of the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = path.display().to_string();
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        SceneDescription::load(path)?
+            .build(dir)
+            .map_err(|e| e.in_file(&file))
+    }
+}
+
+// A scene with the tables picked by their `type` still as plain values
+type RawSceneDescription = SceneDescription<Value, Value, Value>;
+
+impl SceneDescription {
+    pub fn parse(s: &str, format: SceneFormat) -> Result<Self> {
+        decode_tagged(parse(s, format)?)
+    }
+
+    pub fn from_toml_str(toml_str: &str) -> Result<Self> {
+        SceneDescription::parse(toml_str, SceneFormat::Toml)
+    }
+
+    // The extension of the file picks the format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        read(path, SceneFormat::from_path(path))
+            .and_then(decode_tagged)
+            .map_err(|e| e.in_file(&path.display().to_string()))
+    }
+
+    pub fn to_format_string(&self, format: SceneFormat) -> Result<String> {
+        let result = match format {
+            SceneFormat::Toml => toml::to_string(self).map_err(|e| e.into()),
+            SceneFormat::Json => serde_json::to_string_pretty(self)
+                .map(|s| s + "\n")
+                .map_err(|e| e.into()),
+            SceneFormat::Yaml => serde_norway::to_string(self).map_err(|e| e.into()),
+        };
+        result.map_err(|e| SceneError {
+            file: None,
+            path: String::new(),
+            kind: SceneErrorKind::Serialize(e),
+        })
+    }
+
+    pub fn to_toml_string(&self) -> Result<String> {
+        self.to_format_string(SceneFormat::Toml)
+    }
+
+    // Creates the scene, loading any meshes and images it refers to. Relative paths to them are
+    // resolved against `dir`.
+    pub fn build(&self, dir: &Path) -> Result<Scene> {
+        let mut materials = BTreeMap::new();
+        for (i, material) in self.materials.iter().enumerate() {
+            let m = build_material(material, dir, &format!("material[{}]", i))?;
+            materials.insert(material.name.clone(), m);
+        }
+
+        let scene = &self.scene;
+        let camera = build_camera(&scene.camera, "scene.camera")?;
+        let mut surfaces = Vec::new();
+        for (i, surface) in scene.surfaces.iter().enumerate() {
+            let path = format!("scene.surface[{}]", i);
+            surfaces.push(build_surface(surface, &materials, dir, &path)?);
+        }
+        let mut lights = Vec::new();
+        for (i, light) in scene.lights.iter().enumerate() {
+            lights.push(build_light(light, &format!("scene.light[{}]", i))?);
+        }
+        let background = match &scene.background {
+            Some(background) => {
+                if let Some(sun) = build_sun_light(background) {
+                    lights.push(Box::new(sun));
+                }
+                build_background(background, dir, "scene.background")?
+            }
+            None => Background::default(),
+        };
+
+        Ok(Scene::new(
+            surfaces,
+            lights,
+            scene.ambient_const,
+            vec3(scene.ambient_color),
+            background,
+            camera,
+        ))
+    }
+}
+
+// The contents of a config file like `config.toml`: which scene to render, how to render it and
+// where to write the image. Every setting is optional, with the defaults of `Config::default`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    // When loaded from a file, relative paths to the scene and the output files are resolved
+    // against the directory of the file
+    pub scene: Option<PathBuf>,
+    pub settings: RenderSettings,
+    pub out_file: String,
+    pub heatmap_file: Option<String>,
+    pub tone_mapper: ToneMapper,
+}
+
+impl Config {
+    pub fn from_toml_str(toml_str: &str) -> Result<Self> {
+        let file: ConfigFile = parse(toml_str, SceneFormat::Toml)?;
+        file.config.into_config()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file: ConfigFile =
+            read(path, SceneFormat::Toml).map_err(|e| e.in_file(&path.display().to_string()))?;
+        let mut config = file
+            .config
+            .into_config()
+            .map_err(|e| e.in_file(&path.display().to_string()))?;
+        if let Some(dir) = path.parent() {
+            config.scene = config.scene.map(|scene| dir.join(scene));
+            config.out_file = resolve(dir, &config.out_file);
+            config.heatmap_file = config.heatmap_file.map(|file| resolve(dir, &file));
+        }
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            scene: None,
+            settings: RenderSettings::default(),
+            out_file: "image.png".to_owned(),
+            heatmap_file: None,
+            tone_mapper: ToneMapper::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    config: ConfigTable,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigTable {
+    scene: Option<PathBuf>,
+    width: Option<u32>,
+    height: Option<u32>,
+    out_file: Option<String>,
+    heatmap_file: Option<String>,
+    samples: Option<u32>,
+    // Adaptive sampling is enabled by setting a noise threshold
+    noise_threshold: Option<f32>,
+    // 16 times `samples` by default
+    max_samples: Option<u32>,
+    filter: Option<FilterType>,
+    filter_radius: Option<f32>,
+    sampler: Option<SamplerType>,
+    seed: Option<u64>,
+    tone_mapping: Option<ToneMappingType>,
+    white_point: Option<f32>,
+    exposure: Option<f32>,
+    srgb: Option<bool>,
+    reflection_depth: Option<u16>,
+    path_depth: Option<u32>,
+    // 0 uses one thread per core
+    threads: Option<usize>,
+    integrator: Option<Integrator>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FilterType {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ToneMappingType {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+}
+
+impl ConfigTable {
+    fn into_config(self) -> Result<Config> {
+        let default = Config::default();
+        let samples = self.samples.unwrap_or(default.settings.samples);
+        if samples == 0 {
+            return Err(SceneError::invalid(
+                "config.samples",
+                "expected at least 1 sample",
+            ));
+        }
+        let adaptive = self.noise_threshold.map(|threshold| AdaptiveSampling {
+            threshold,
+            max_samples: self.max_samples,
+        });
+        let radius = self.filter_radius;
+        if radius.is_some_and(|radius| radius <= 0. || radius.is_nan()) {
+            let message = "expected a filter radius above 0";
+            return Err(SceneError::invalid("config.filter_radius", message));
+        }
+        let filter = match self.filter {
+            None => default.settings.filter,
+            Some(FilterType::Box) => Filter::Box {
+                radius: radius.unwrap_or(0.5),
+            },
+            Some(FilterType::Tent) => Filter::Tent {
+                radius: radius.unwrap_or(1.),
+            },
+            Some(FilterType::Gaussian) => Filter::Gaussian {
+                radius: radius.unwrap_or(1.5),
+            },
+            Some(FilterType::Mitchell) => Filter::Mitchell {
+                radius: radius.unwrap_or(2.),
+            },
+        };
+        let tone_mapping = match self.tone_mapping {
+            None => default.tone_mapper.tone_mapping,
+            Some(ToneMappingType::Clamp) => ToneMapping::Clamp,
+            Some(ToneMappingType::Reinhard) => ToneMapping::Reinhard,
+            Some(ToneMappingType::ExtendedReinhard) => ToneMapping::ExtendedReinhard {
+                white: self.white_point,
+            },
+            Some(ToneMappingType::Aces) => ToneMapping::Aces,
+        };
+        let tone_mapper = ToneMapper::new(
+            tone_mapping,
+            self.exposure.unwrap_or(default.tone_mapper.exposure),
+            self.srgb.unwrap_or(default.tone_mapper.srgb),
+        );
+
+        Ok(Config {
+            scene: self.scene,
+            settings: RenderSettings {
+                width: self.width.unwrap_or(default.settings.width),
+                height: self.height.unwrap_or(default.settings.height),
+                max_depth: self.reflection_depth.unwrap_or(default.settings.max_depth),
+                path_depth: self.path_depth.unwrap_or(default.settings.path_depth),
+                threads: self.threads.unwrap_or(default.settings.threads),
+                integrator: self.integrator.unwrap_or(default.settings.integrator),
+                samples,
+                adaptive,
+                filter,
+                sampler: self.sampler.unwrap_or(default.settings.sampler),
+                seed: self.seed.unwrap_or(default.settings.seed),
+            },
+            out_file: self.out_file.unwrap_or(default.out_file),
+            heatmap_file: self.heatmap_file,
+            tone_mapper,
+        })
+    }
+}
+
+fn read<T: DeserializeOwned>(path: &Path, format: SceneFormat) -> Result<T> {
+    let s = fs::read_to_string(path).map_err(|e| SceneError {
+        file: None,
+        path: String::new(),
+        kind: SceneErrorKind::Io(e),
+    })?;
+    parse(&s, format)
+}
+
+fn parse<T: DeserializeOwned>(s: &str, format: SceneFormat) -> Result<T> {
+    match format {
+        SceneFormat::Toml => {
+            serde_path_to_error::deserialize(&mut toml::Deserializer::new(s)).map_err(parse_error)
+        }
+        SceneFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(s);
+            let value = serde_path_to_error::deserialize(&mut deserializer).map_err(parse_error)?;
+            // Only whitespace can follow the value
+            deserializer.end().map_err(|e| SceneError {
+                file: None,
+                path: String::new(),
+                kind: SceneErrorKind::Parse(Box::new(e)),
+            })?;
+            Ok(value)
+        }
+        SceneFormat::Yaml => {
+            serde_path_to_error::deserialize(serde_norway::Deserializer::from_str(s))
+                .map_err(parse_error)
+        }
+    }
+}
+
+// Keeps track of where in the file deserialization failed
+fn parse_error<E: Error + Send + Sync + 'static>(e: serde_path_to_error::Error<E>) -> SceneError {
+    let mut path = e.path().to_string();
+    let e = e.into_inner();
+    // The path of the root is "."
+    if path == "." {
+        path.clear();
+    }
+    let message = e.to_string();
+    // Point at the missing key rather than the table it's missing from
+    if let Some(key) = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next())
+    {
+        path = join_path(&path, key);
+    }
+    // YAML errors start with a path of their own, which leaves out the indices into arrays
+    let kind = match message.split_once(": ") {
+        Some((prefix, rest)) if !prefix.is_empty() && path.starts_with(prefix) => {
+            SceneErrorKind::Parse(rest.into())
+        }
+        _ => SceneErrorKind::Parse(Box::new(e)),
+    };
+    SceneError {
+        file: None,
+        path,
+        kind,
+    }
+}
+
+// `path` followed by `key`, which may be an index like `[2]`
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else if key.is_empty() || key.starts_with('[') {
+        format!("{}{}", path, key)
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// Deserializes a value that was read as part of the file at `path`
+fn decode_value<T: DeserializeOwned>(value: Value, path: &str) -> Result<T> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let mut error = parse_error(e);
+        error.path = join_path(path, &error.path);
+        error
+    })
+}
+
+// Reads the tables picked by their `type`. Deserializing the enums directly would buffer the
+// table to find the type first, which loses track of the key an error is in.
+fn decode_tagged(raw: RawSceneDescription) -> Result<SceneDescription> {
+    let world = raw.scene;
+    let background = world
+        .background
+        .map(|background| decode_background(background, "scene.background"))
+        .transpose()?;
+    let surfaces = world
+        .surfaces
+        .into_iter()
+        .enumerate()
+        .map(|(i, surface)| decode_surface(surface, &format!("scene.surface[{}]", i)))
+        .collect::<Result<_>>()?;
+    let lights = world
+        .lights
+        .into_iter()
+        .enumerate()
+        .map(|(i, light)| decode_light(light, &format!("scene.light[{}]", i)))
+        .collect::<Result<_>>()?;
+    Ok(SceneDescription {
+        materials: raw.materials,
+        scene: WorldDescription {
+            ambient_const: world.ambient_const,
+            ambient_color: world.ambient_color,
+            camera: world.camera,
+            background,
+            surfaces,
+            lights,
+        },
+    })
+}
+
+fn decode_background(value: Value, path: &str) -> Result<BackgroundDescription> {
+    let (kind, fields) = split_type(value, path)?;
+    Ok(match kind.as_str() {
+        "color" => BackgroundDescription::Color(decode_value(fields, path)?),
+        "gradient" => BackgroundDescription::Gradient(decode_value(fields, path)?),
+        "environment" => BackgroundDescription::Environment(decode_value(fields, path)?),
+        "sky" => BackgroundDescription::Sky(decode_value(fields, path)?),
+        _ => return Err(unknown_type::<BackgroundDescription>(&kind, path)),
+    })
+}
+
+fn decode_surface(value: Value, path: &str) -> Result<SurfaceDescription> {
+    let (kind, fields) = split_type(value, path)?;
+    Ok(match kind.as_str() {
+        "plane" => SurfaceDescription::Plane(decode_value(fields, path)?),
+        "sphere" => SurfaceDescription::Sphere(decode_value(fields, path)?),
+        "mesh" => SurfaceDescription::Mesh(decode_value(fields, path)?),
+        _ => return Err(unknown_type::<SurfaceDescription>(&kind, path)),
+    })
+}
+
+fn decode_light(value: Value, path: &str) -> Result<LightDescription> {
+    let (kind, fields) = split_type(value, path)?;
+    Ok(match kind.as_str() {
+        "point" => LightDescription::Point(decode_value(fields, path)?),
+        "area" => LightDescription::Area(decode_value(fields, path)?),
+        "directional" => LightDescription::Directional(decode_value(fields, path)?),
+        "spot" => LightDescription::Spot(decode_value(fields, path)?),
+        _ => return Err(unknown_type::<LightDescription>(&kind, path)),
+    })
+}
+
+// Separates the `type` of a table from the rest of its keys
+fn split_type(value: Value, path: &str) -> Result<(String, Value)> {
+    let mut table: Map<String, Value> = decode_value(value, path)?;
+    let type_path = join_path(path, "type");
+    let kind = match table.remove("type") {
+        Some(kind) => decode_value(kind, &type_path)?,
+        None => return Err(SceneError::invalid(type_path, "missing field `type`")),
+    };
+    Ok((kind, Value::Object(table)))
+}
+
+// The error serde gives for a `type` that isn't one of the variants of `T`, which lists them
+fn unknown_type<T: DeserializeOwned>(kind: &str, path: &str) -> SceneError {
+    let message = match serde_json::from_value::<T>(json!({ "type": kind })) {
+        Err(e) => e.to_string(),
+        Ok(_) => format!("unsupported type `{}`", kind),
+    };
+    SceneError::invalid(join_path(path, "type"), message)
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+// The path of a file named in a scene, which is either absolute or relative to `dir`
+fn resolve(dir: &Path, file: &str) -> String {
+    dir.join(file).display().to_string()
+}
+
+fn build_material(material: &MaterialDescription, dir: &Path, path: &str) -> Result<Material> {
+    let texture: Option<Box<dyn Texture>> = if let Some(checkerboard) = material.checkerboard {
+        Some(Box::new(CheckerboardTexture::new(checkerboard)))
+    } else if let Some(texture) = &material.texture {
+        let texture = resolve(dir, texture);
+        let image = ImageTexture::from_file(&texture).map_err(|e| {
+            let message = format!("couldn't load {}: {}", texture, e);
+            SceneError::invalid(format!("{}.texture", path), message)
+        })?;
+        Some(Box::new(image))
+    } else {
+        None
+    };
+
+    let normal_map =
+        material
+            .normal_map
+            .map(|[seed, octaves, wavelength, persistence, lacunarity]| {
+                NormalMap::new(
+                    seed as u32,
+                    octaves as usize,
+                    wavelength,
+                    persistence,
+                    lacunarity,
+                )
+            });
+
+    let displacement_map =
+        material
+            .displacement_map
+            .map(|[seed, octaves, wavelength, persistence, lacunarity]| {
+                DisplacementMap::new(
+                    seed as u32,
+                    octaves as usize,
+                    wavelength,
+                    persistence,
+                    lacunarity,
+                )
+            });
+    Ok(Material::new(
+        vec3(material.color),
+        material.diffuse,
+        material.specular,
+        material.glossiness,
+        material.reflectivity,
+        material.transparency,
+        material.ior,
+        vec3(material.emission),
+        material.emission_strength,
+        texture,
+        normal_map,
+        displacement_map,
+    ))
+}
+
+fn build_background(
+    background: &BackgroundDescription,
+    dir: &Path,
+    path: &str,
+) -> Result<Background> {
+    Ok(match background {
+        BackgroundDescription::Color(ColorBackgroundDescription { color }) => {
+            Background::Color(vec3(*color))
+        }
+        BackgroundDescription::Gradient(GradientBackgroundDescription { top, bottom }) => {
+            Background::Gradient {
+                top: vec3(*top),
+                bottom: vec3(*bottom),
+            }
+        }
+        BackgroundDescription::Environment(EnvironmentDescription {
+            file,
+            intensity,
+            rotation,
+        }) => {
+            let file = resolve(dir, file);
+            let map = EnvironmentMap::from_file(&file, *intensity, *rotation).map_err(|e| {
+                let message = format!("couldn't load {}: {}", file, e);
+                SceneError::invalid(format!("{}.file", path), message)
+            })?;
+            Background::Environment(map)
+        }
+        BackgroundDescription::Sky(SkyDescription {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            intensity,
+            ..
+        }) => Background::Sky(Sky::new(
+            *sun_elevation,
+            *sun_azimuth,
+            *turbidity,
+            *intensity,
+        )),
+    })
+}
+
+// A sky can bring its own sun, a directional light shining from the sun's position in the sky
+// with the color of the sky around it
+fn build_sun_light(background: &BackgroundDescription) -> Option<DirectionalLight> {
+    match *background {
+        BackgroundDescription::Sky(SkyDescription {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            intensity,
+            sun_light: true,
+            sun_intensity,
+        }) => {
+            let sky = Sky::new(sun_elevation, sun_azimuth, turbidity, intensity);
+            Some(DirectionalLight::new(
+                -sky.sun_dir(),
+                sky.sun_color(),
+                sun_intensity,
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn build_camera(camera: &CameraDescription, path: &str) -> Result<Camera> {
+    let pos = vec3(camera.pos);
+    let lookat = vec3(camera.lookat);
+    let projection = match camera.projection {
+        ProjectionType::Perspective => Projection::Perspective(match camera.fov {
+            Some(fov) => match camera.fov_axis {
+                FovAxis::Vertical => Fov::Vertical(fov),
+                FovAxis::Horizontal => Fov::Horizontal(fov),
+            },
+            None => Fov::default(),
+        }),
+        ProjectionType::Orthographic => Projection::Orthographic {
+            size: camera.ortho_size.ok_or_else(|| {
+                let message = "missing field `ortho_size` for an orthographic camera";
+                SceneError::invalid(format!("{}.ortho_size", path), message)
+            })?,
+        },
+        ProjectionType::Fisheye => Projection::Fisheye {
+            fov: camera.fov.unwrap_or(180.),
+        },
+        ProjectionType::Equirectangular => Projection::Equirectangular,
+    };
+    // Focus on the point the camera is looking at by default
+    let focus_distance = camera.focus_distance.unwrap_or((lookat - pos).norm());
+    Ok(Camera::from_lookat(
+        pos,
+        lookat,
+        vec3(camera.up),
+        projection,
+        camera.aperture,
+        focus_distance,
+    ))
+}
+
+fn build_surface(
+    surface: &SurfaceDescription,
+    materials: &BTreeMap<String, Material>,
+    dir: &Path,
+    path: &str,
+) -> Result<Box<dyn Surface>> {
+    let material_name = surface.material();
+    let material = match materials.get(material_name) {
+        Some(material) => material.clone(),
+        None => {
+            let message = format!("unknown material \"{}\"", material_name);
+            return Err(SceneError::invalid(format!("{}.material", path), message));
+        }
+    };
+
+    let transform = surface.transform();
+    match surface {
+        SurfaceDescription::Plane(PlaneDescription { pos, normal, .. }) => {
+            let plane = Plane::new(vec3(*pos), vec3(*normal), material);
+            place(plane, transform, path)
+        }
+        SurfaceDescription::Sphere(SphereDescription { pos, radius, .. }) => {
+            let sphere = Sphere::new(vec3(*pos), *radius, material);
+            place(sphere, transform, path)
+        }
+        SurfaceDescription::Mesh(MeshDescription { file, .. }) => {
+            let file = resolve(dir, file);
+            let mesh = TriangleMesh::from_obj(&file, material).map_err(|e| {
+                let message = format!("couldn't load {}: {}", file, e);
+                SceneError::invalid(format!("{}.file", path), message)
+            })?;
+            place(mesh, transform, path)
+        }
+    }
+}
+
+// Wraps the surface in a `Transformed` if it has a transform
+fn place<S: Surface + 'static>(
+    surface: S,
+    transform: Option<&TransformDescription>,
+    path: &str,
+) -> Result<Box<dyn Surface>> {
+    let transform = match transform {
+        Some(transform) => transform,
+        None => return Ok(Box::new(surface)),
+    };
+    match Transformed::new(surface, build_transform(transform)) {
+        Some(transformed) => Ok(Box::new(transformed)),
+        None => Err(SceneError::invalid(
+            format!("{}.transform", path),
+            "the transform can't be inverted",
+        )),
+    }
+}
+
+fn build_transform(transform: &TransformDescription) -> Matrix4<f32> {
+    let scale = match transform.scale {
+        Some(ScaleDescription::Uniform(s)) => Vec3::repeat(s),
+        Some(ScaleDescription::Axes(s)) => vec3(s),
+        None => Vec3::repeat(1.),
+    };
+    let [x, y, z] = transform.rotate.unwrap_or([0.; 3]);
+    let rotation = Rotation3::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians());
+    let translation = vec3(transform.translate.unwrap_or([0.; 3]));
+    let matrix = match transform.matrix {
+        Some(rows) => Matrix4::from_fn(|i, j| rows[i][j]),
+        None => Matrix4::identity(),
+    };
+    matrix
+        * Matrix4::new_translation(&translation)
+        * rotation.to_homogeneous()
+        * Matrix4::new_nonuniform_scaling(&scale)
+}
+
+fn build_light(light: &LightDescription, path: &str) -> Result<Box<dyn Light>> {
+    Ok(match *light {
+        LightDescription::Point(PointLightDescription {
+            pos,
+            color,
+            intensity,
+            attenuation,
+        }) => Box::new(PointLight::new(
+            vec3(pos),
+            vec3(color),
+            intensity,
+            build_attenuation(attenuation),
+        )),
+        LightDescription::Area(AreaLightDescription {
+            pos,
+            color,
+            intensity,
+            samples,
+            shape,
+            u,
+            v,
+            radius,
+            attenuation,
+        }) => {
+            let missing = |key: &str| {
+                let shape = match shape {
+                    AreaShapeType::Rect => "rect",
+                    AreaShapeType::Sphere => "sphere",
+                };
+                let message = format!("missing field `{}` for a {} area light", key, shape);
+                SceneError::invalid(format!("{}.{}", path, key), message)
+            };
+            let center = vec3(pos);
+            let shape = match shape {
+                AreaShapeType::Rect => AreaShape::Rect {
+                    center,
+                    u: vec3(u.ok_or_else(|| missing("u"))?),
+                    v: vec3(v.ok_or_else(|| missing("v"))?),
+                },
+                AreaShapeType::Sphere => AreaShape::Sphere {
+                    center,
+                    radius: radius.ok_or_else(|| missing("radius"))?,
+                },
+            };
+            Box::new(AreaLight::new(
+                shape,
+                vec3(color),
+                intensity,
+                samples,
+                build_attenuation(attenuation),
+            ))
+        }
+        LightDescription::Directional(DirectionalLightDescription {
+            dir,
+            color,
+            intensity,
+        }) => Box::new(DirectionalLight::new(vec3(dir), vec3(color), intensity)),
+        LightDescription::Spot(SpotLightDescription {
+            pos,
+            dir,
+            color,
+            intensity,
+            inner_angle,
+            outer_angle,
+            falloff,
+            attenuation,
+        }) => {
+            if falloff < 0. {
+                let message = "expected a falloff of at least 0";
+                return Err(SceneError::invalid(format!("{}.falloff", path), message));
+            }
+            Box::new(SpotLight::new(
+                vec3(pos),
+                vec3(dir),
+                vec3(color),
+                intensity,
+                inner_angle,
+                outer_angle,
+                falloff,
+                build_attenuation(attenuation),
+            ))
+        }
+    })
+}
+
+fn build_attenuation(attenuation: AttenuationDescription) -> Attenuation {
+    match attenuation {
+        AttenuationDescription::Named(AttenuationType::None) => Attenuation::none(),
+        AttenuationDescription::Named(AttenuationType::Linear) => Attenuation::linear(),
+        AttenuationDescription::Named(AttenuationType::InverseSquare) => {
+            Attenuation::inverse_square()
+        }
+        AttenuationDescription::Coefficients([constant, linear, quadratic]) => {
+            Attenuation::new(constant, linear, quadratic)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"
+[[material]]
+name = "white"
+color = [255, 255, 255]
+diffuse = 1.0
+specular = 0.0
+glossiness = 0.0
+reflectivity = 0.0
+
+[scene]
+ambient_const = 0.1
+ambient_color = [255, 255, 255]
+
+[scene.camera]
+pos = [0.0, 1.0, -5.0]
+lookat = [0.0, 1.0, 0.0]
+up = [0.0, 1.0, 0.0]
+
+[[scene.surface]]
+type = "plane"
+material = "white"
+pos = [0.0, 0.0, 0.0]
+normal = [0.0, 1.0, 0.0]
+
+[[scene.surface]]
+type = "sphere"
+material = "white"
+pos = [0.0, 1.0, 0.0]
+radius = 1.0
+
+[[scene.light]]
+type = "point"
+pos = [2.0, 3.0, -4.0]
+color = [255, 255, 255]
+intensity = 2.0
+"#;
+
+    // The path of the error loading SCENE with `from` replaced by `to`
+    fn error_path(from: &str, to: &str) -> String {
+        assert!(SCENE.contains(from));
+        SceneDescription::from_toml_str(&SCENE.replace(from, to))
+            .unwrap_err()
+            .path
+    }
+
+    #[test]
+    fn scene_parses() {
+        let description = SceneDescription::from_toml_str(SCENE).unwrap();
+        assert_eq!(description.scene.surfaces.len(), 2);
+        assert_eq!(description.scene.lights.len(), 1);
+    }
+
+    #[test]
+    fn errors_point_at_the_key_in_surfaces_and_lights() {
+        assert_eq!(
+            error_path("radius = 1.0", "radius = \"big\""),
+            "scene.surface[1].radius"
+        );
+        assert_eq!(error_path("radius = 1.0", ""), "scene.surface[1].radius");
+        assert_eq!(
+            error_path("intensity = 2.0", "intensity = true"),
+            "scene.light[0].intensity"
+        );
+        assert_eq!(
+            error_path("intensity = 2.0", ""),
+            "scene.light[0].intensity"
+        );
+        assert_eq!(
+            error_path("type = \"sphere\"", "type = \"cube\""),
+            "scene.surface[1].type"
+        );
+    }
+
+    #[test]
+    fn config_rejects_zero_samples_and_filter_radius() {
+        let error_path = |config: &str| Config::from_toml_str(config).unwrap_err().path;
+        assert_eq!(error_path("[config]\nsamples = 0"), "config.samples");
+        assert_eq!(
+            error_path("[config]\nfilter = \"tent\"\nfilter_radius = 0.0"),
+            "config.filter_radius"
+        );
+    }
+
+    // A TOML scene written out in `format`
+    fn in_format(toml_str: &str, format: SceneFormat) -> String {
+        let value: Value = toml::from_str(toml_str).unwrap();
+        match format {
+            SceneFormat::Toml => toml_str.to_owned(),
+            SceneFormat::Json => serde_json::to_string_pretty(&value).unwrap(),
+            SceneFormat::Yaml => serde_norway::to_string(&value).unwrap(),
+        }
+    }
+
+    #[test]
+    fn formats_load_the_same_scene_and_errors() {
+        let formats = [SceneFormat::Toml, SceneFormat::Json, SceneFormat::Yaml];
+        let dump = |format| {
+            SceneDescription::parse(&in_format(SCENE, format), format)
+                .unwrap()
+                .to_format_string(SceneFormat::Json)
+                .unwrap()
+        };
+        let error_path = |format, from: &str, to: &str| {
+            let scene = in_format(&SCENE.replace(from, to), format);
+            SceneDescription::parse(&scene, format).unwrap_err().path
+        };
+        for &format in formats.iter() {
+            assert_eq!(dump(format), dump(SceneFormat::Toml));
+            assert_eq!(
+                error_path(format, "radius = 1.0", "radius = \"big\""),
+                "scene.surface[1].radius"
+            );
+            assert_eq!(
+                error_path(format, "radius = 1.0", ""),
+                "scene.surface[1].radius"
+            );
+            assert_eq!(
+                error_path(format, "intensity = 2.0", "intensity = 2.0\nbogus = 1"),
+                "scene.light[0].bogus"
+            );
+            assert_eq!(
+                error_path(
+                    format,
+                    "[0.0, 1.0, 0.0]\nradius",
+                    "[0.0, \"up\", 0.0]\nradius"
+                ),
+                "scene.surface[1].pos[1]"
+            );
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::f32;
+
+use crate::Vec3;
+
+// Converts the luminance of the model in kcd/m^2 to the scale of the renderer, where a clear sky
+// at midday is around 1 (255) at the zenith
+const LUMINANCE_SCALE: f32 = 0.1;
+
+// Coefficients of the Perez sky distribution, one row per parameter A to E, as a linear function
+// of the turbidity (slope, intercept)
+const PEREZ_LUMINANCE: [[f32; 2]; 5] = [
+    [0.1787, -1.4630],
+    [-0.3554, 0.4275],
+    [-0.0227, 5.3251],
+    [0.1206, -2.5771],
+    [-0.0670, 0.3703],
+];
+const PEREZ_X: [[f32; 2]; 5] = [
+    [-0.0193, -0.2592],
+    [-0.0665, 0.0008],
+    [-0.0004, 0.2125],
+    [-0.0641, -0.8989],
+    [-0.0033, 0.0452],
+];
+const PEREZ_Y: [[f32; 2]; 5] = [
+    [-0.0167, -0.2608],
+    [-0.0950, 0.0092],
+    [-0.0079, 0.2102],
+    [-0.0441, -1.6537],
+    [-0.0109, 0.0529],
+];
+
+// Chromaticity of the zenith as a polynomial in the turbidity (rows for T^2, T and 1) and the
+// zenith angle of the sun (columns for theta^3, theta^2, theta and 1)
+const ZENITH_X: [[f32; 4]; 3] = [
+    [0.00166, -0.00375, 0.00209, 0.],
+    [-0.02903, 0.06377, -0.03202, 0.00394],
+    [0.11693, -0.21196, 0.06052, 0.25886],
+];
+const ZENITH_Y: [[f32; 4]; 3] = [
+    [0.00275, -0.00610, 0.00317, 0.],
+    [-0.04214, 0.08970, -0.04153, 0.00516],
+    [0.15346, -0.26756, 0.06670, 0.26688],
+];
+
+// Preetham's analytic model of a clear daylight sky ("A Practical Analytic Model for Daylight").
+// Below the horizon the sky is mirrored, so there is no hard edge when nothing covers it.
+#[derive(Clone, Debug)]
+pub struct Sky {
+    // Unit vector pointing at the sun
+    sun_dir: Vec3,
+    // Zenith angle of the sun in radians
+    sun_theta: f32,
+    intensity: f32,
+    // Perez coefficients and zenith values for the luminance and the x and y chromaticities
+    perez: [[f32; 5]; 3],
+    zenith: [f32; 3],
+}
+
+impl Sky {
+    // `sun_elevation` is the angle of the sun above the horizon and `sun_azimuth` the angle
+    // around the y axis from +z towards +x, both in degrees. `turbidity` goes from about 2 for a
+    // very clear sky to 10 for a hazy one.
+    pub fn new(sun_elevation: f32, sun_azimuth: f32, turbidity: f32, intensity: f32) -> Self {
+        let elevation = sun_elevation.to_radians();
+        let azimuth = sun_azimuth.to_radians();
+        let sun_dir = Vec3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        );
+        let t = turbidity;
+        let theta = f32::consts::FRAC_PI_2 - elevation.max(0.);
+
+        let chi = (4. / 9. - t / 120.) * (f32::consts::PI - 2. * theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith = [
+            zenith_luminance,
+            zenith_chromaticity(&ZENITH_X, t, theta),
+            zenith_chromaticity(&ZENITH_Y, t, theta),
+        ];
+        let perez = [
+            perez_coefficients(&PEREZ_LUMINANCE, t),
+            perez_coefficients(&PEREZ_X, t),
+            perez_coefficients(&PEREZ_Y, t),
+        ];
+
+        Sky {
+            sun_dir,
+            sun_theta: theta,
+            intensity,
+            perez,
+            zenith,
+        }
+    }
+
+    // Unit vector pointing at the sun
+    pub fn sun_dir(&self) -> &Vec3 {
+        &self.sun_dir
+    }
+
+    // Color of the sky right next to the sun, scaled so the largest channel is 255, which makes
+    // a good color for a directional light standing in for the sun
+    pub fn sun_color(&self) -> Vec3 {
+        let color = self.color(&self.sun_dir);
+        let max = color.max();
+        if max <= 0. {
+            return Vec3::repeat(255.);
+        }
+        color * (255. / max)
+    }
+
+    // Radiance arriving from direction `dir`, which has to be normalized
+    pub fn color(&self, dir: &Vec3) -> Vec3 {
+        let dir = Vec3::new(dir.x, dir.y.abs(), dir.z);
+        // The model breaks down at the horizon
+        let cos_theta = dir.y.max(0.01);
+        let cos_gamma = dir.dot(&self.sun_dir).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+
+        let mut xyy = [0.; 3];
+        for (i, value) in xyy.iter_mut().enumerate() {
+            *value = self.zenith[i] * perez(&self.perez[i], cos_theta, gamma, cos_gamma)
+                / perez(&self.perez[i], 1., self.sun_theta, self.sun_theta.cos());
+        }
+        let [luminance, x, y] = xyy;
+        xyy_to_rgb(x, y, luminance.max(0.) * LUMINANCE_SCALE) * (255. * self.intensity)
+    }
+}
+
+fn perez_coefficients(rows: &[[f32; 2]; 5], turbidity: f32) -> [f32; 5] {
+    let mut coefficients = [0.; 5];
+    for (c, row) in coefficients.iter_mut().zip(rows.iter()) {
+        *c = row[0] * turbidity + row[1];
+    }
+    coefficients
+}
+
+fn zenith_chromaticity(rows: &[[f32; 4]; 3], turbidity: f32, theta: f32) -> f32 {
+    let t = [turbidity * turbidity, turbidity, 1.];
+    let th = [theta * theta * theta, theta * theta, theta, 1.];
+    let mut value = 0.;
+    for (i, row) in rows.iter().enumerate() {
+        for (j, c) in row.iter().enumerate() {
+            value += t[i] * c * th[j];
+        }
+    }
+    value
+}
+
+// Relative brightness of the sky at zenith angle theta and angle gamma from the sun
+fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32, cos_gamma: f32) -> f32 {
+    (1. + c[0] * (c[1] / cos_theta).exp())
+        * (1. + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+// CIE xyY to linear sRGB
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    if y <= 0. {
+        return Vec3::zeros();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+    let rgb = Vec3::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    );
+    rgb.map(|c| c.max(0.))
+}
@@ -1,14 +1,46 @@
 use std::f32;
+use std::io;
 
+use crate::bvh::{Aabb, Bvh};
+use crate::light::{AreaLight, AreaShape};
 use crate::material::Material;
+use crate::obj::{self, Face, Obj};
 use crate::ray::{Intersection, Ray};
 use crate::Vec3;
 
-pub trait Surface {
+use nalgebra::{Matrix3, Matrix4, Point3, U3};
+
+pub trait Surface: Send + Sync {
     fn intersect(&self, ray: &Ray) -> Option<Intersection>;
     fn material(&self) -> &Material;
+    // Infinite surfaces return `None` and are tested separately from the BVH
+    fn bounding_box(&self) -> Option<Aabb>;
     // For debugging
     fn name(&self) -> &'static str;
+    // Emissive surfaces with a finite area return a light covering the surface, so the scene can
+    // sample it like any other light
+    fn light(&self) -> Option<AreaLight> {
+        None
+    }
+}
+
+// Shadow rays traced towards an emissive surface per hit
+const EMISSIVE_LIGHT_SAMPLES: u32 = 16;
+
+// `shape` returns `None` for surfaces without any area to sample
+fn emissive_light(
+    material: &Material,
+    shape: impl FnOnce() -> Option<AreaShape>,
+) -> Option<AreaLight> {
+    if !material.is_emissive() {
+        return None;
+    }
+    Some(AreaLight::emissive(
+        shape()?,
+        *material.emission(),
+        material.emission_strength(),
+        EMISSIVE_LIGHT_SAMPLES,
+    ))
 }
 
 pub struct Sphere {
@@ -36,6 +68,20 @@ impl Surface for Sphere {
         &self.material
     }
 
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::repeat(self.radius);
+        Some(Aabb::new(self.pos - r, self.pos + r))
+    }
+
+    fn light(&self) -> Option<AreaLight> {
+        emissive_light(&self.material, || {
+            Some(AreaShape::Sphere {
+                center: self.pos,
+                radius: self.radius,
+            })
+        })
+    }
+
     fn intersect(&self, ray: &Ray) -> Option<Intersection> {
         let center_offset = ray.origin - self.pos;
         let b = 2. * ray.dir.dot(&center_offset);
@@ -110,6 +156,10 @@ impl Surface for Plane {
         &self.material
     }
 
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
     fn intersect(&self, ray: &Ray) -> Option<Intersection> {
         let denom = ray.dir.dot(&self.normal);
         if denom == 0. {
@@ -143,3 +193,246 @@ impl Surface for Plane {
         }
     }
 }
+
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    normals: Option<[Vec3; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vec3; 3], normals: Option<[Vec3; 3]>, material: Material) -> Self {
+        Triangle {
+            vertices,
+            normals,
+            material,
+        }
+    }
+}
+
+impl Surface for Triangle {
+    fn name(&self) -> &'static str {
+        "Triangle"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices.iter().copied()))
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        intersect_triangle(ray, &self.vertices, self.normals.as_ref(), &self.material)
+    }
+
+    fn light(&self) -> Option<AreaLight> {
+        emissive_light(&self.material, || AreaShape::triangles(vec![self.vertices]))
+    }
+}
+
+pub struct TriangleMesh {
+    vertices: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    faces: Vec<Face>,
+    bvh: Bvh,
+    material: Material,
+}
+
+impl TriangleMesh {
+    pub fn new(obj: Obj, material: Material) -> Self {
+        let boxes = obj
+            .faces
+            .iter()
+            .map(|face| Aabb::from_points(face.vertices.iter().map(|&i| obj.vertices[i])))
+            .collect::<Vec<_>>();
+        TriangleMesh {
+            bvh: Bvh::new(&boxes),
+            vertices: obj.vertices,
+            normals: obj.normals,
+            faces: obj.faces,
+            material,
+        }
+    }
+
+    pub fn from_obj(filename: &str, material: Material) -> io::Result<Self> {
+        Ok(TriangleMesh::new(obj::load(filename)?, material))
+    }
+
+    fn intersect_face(&self, ray: &Ray, face: &Face) -> Option<Intersection> {
+        let [a, b, c] = face.vertices;
+        let vertices = [self.vertices[a], self.vertices[b], self.vertices[c]];
+        let normals = face
+            .normals
+            .map(|[a, b, c]| [self.normals[a], self.normals[b], self.normals[c]]);
+        intersect_triangle(ray, &vertices, normals.as_ref(), &self.material)
+    }
+}
+
+impl Surface for TriangleMesh {
+    fn name(&self) -> &'static str {
+        "TriangleMesh"
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+
+    fn light(&self) -> Option<AreaLight> {
+        emissive_light(&self.material, || {
+            AreaShape::triangles(
+                self.faces
+                    .iter()
+                    .map(|face| {
+                        let [a, b, c] = face.vertices;
+                        [self.vertices[a], self.vertices[b], self.vertices[c]]
+                    })
+                    .collect(),
+            )
+        })
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        self.bvh
+            .intersect(ray, |i| self.intersect_face(ray, &self.faces[i]))
+            .map(|(_, hit)| hit)
+    }
+}
+
+// Places a surface in the scene with an affine transformation, which can rotate it or scale it
+// unevenly, turning a sphere into an ellipsoid. Rays are moved into the space of the surface for
+// the intersection, and the hit is moved back out.
+pub struct Transformed<S: Surface> {
+    surface: S,
+    // Object to world space and back
+    to_world: Matrix4<f32>,
+    to_object: Matrix4<f32>,
+    // Inverse transpose of the linear part of `to_world`, which keeps normals perpendicular to
+    // the surface
+    normal_matrix: Matrix3<f32>,
+}
+
+impl<S: Surface> Transformed<S> {
+    // Returns `None` if `to_world` can't be inverted, like a scale of zero
+    pub fn new(surface: S, to_world: Matrix4<f32>) -> Option<Self> {
+        let to_object = to_world.try_inverse()?;
+        let normal_matrix = to_object.fixed_slice::<U3, U3>(0, 0).transpose();
+        Some(Transformed {
+            surface,
+            to_world,
+            to_object,
+            normal_matrix,
+        })
+    }
+}
+
+impl<S: Surface> Surface for Transformed<S> {
+    fn name(&self) -> &'static str {
+        self.surface.name()
+    }
+
+    fn material(&self) -> &Material {
+        self.surface.material()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.surface.bounding_box()?;
+        let corners = (0..8).map(|i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            );
+            self.to_world.transform_point(&Point3::from(corner)).coords
+        });
+        Some(Aabb::from_points(corners))
+    }
+
+    fn light(&self) -> Option<AreaLight> {
+        self.surface.light()?.transformed(&self.to_world)
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+        let origin = self
+            .to_object
+            .transform_point(&Point3::from(ray.origin))
+            .coords;
+        let dir = self.to_object.transform_vector(&ray.dir);
+        // A unit step along the ray is this long in object space
+        let scale = dir.norm();
+        let hit = self.surface.intersect(&Ray::new(origin, dir))?;
+
+        let pos = self.to_world.transform_point(&Point3::from(hit.pos)).coords;
+        let normal = (self.normal_matrix * hit.normal).normalize();
+        Some(Intersection::new(
+            pos,
+            normal,
+            hit.dist / scale,
+            hit.u,
+            hit.v,
+        ))
+    }
+}
+
+// Möller-Trumbore intersection. The barycentric coordinates of the hit are returned as u and v,
+// and are used to interpolate the vertex normals if there are any.
+fn intersect_triangle(
+    ray: &Ray,
+    vertices: &[Vec3; 3],
+    normals: Option<&[Vec3; 3]>,
+    material: &Material,
+) -> Option<Intersection> {
+    let [v0, v1, v2] = vertices;
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    let p = ray.dir.cross(&edge2);
+    let det = edge1.dot(&p);
+    if det.abs() < f32::EPSILON {
+        // The ray is parallel to the triangle
+        return None;
+    }
+    let inv_det = 1. / det;
+
+    let t = ray.origin - v0;
+    let u = t.dot(&p) * inv_det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+
+    let q = t.cross(&edge1);
+    let v = ray.dir.dot(&q) * inv_det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+
+    let d = edge2.dot(&q) * inv_det;
+    if d <= 0. {
+        return None;
+    }
+
+    let pos = ray.origin + ray.dir * d;
+    let normal = match normals {
+        Some([n0, n1, n2]) => (n0 * (1. - u - v) + n1 * u + n2 * v).normalize(),
+        None => edge1.cross(&edge2).normalize(),
+    };
+
+    let normal = if material.has_normal_map() {
+        material.apply_normal_map(&normal, &pos)
+    } else {
+        normal
+    };
+
+    let pos = if material.has_displacement_map() {
+        material.apply_displacement_map(&pos)
+    } else {
+        pos
+    };
+
+    Some(Intersection::new(pos, normal, d, u, v))
+}
@@ -1,8 +1,11 @@
+use std::io;
+
+use crate::framebuffer::to_io_error;
 use crate::Vec3;
 
 use image::{self, DynamicImage, RgbImage};
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn color(&self, u: f32, v: f32) -> Vec3;
     fn clone_(&self) -> Box<dyn Texture>;
 }
@@ -58,11 +61,18 @@ pub struct ImageTexture {
 
 impl ImageTexture {
     pub fn new(filename: &str) -> Self {
-        let image = image::open(filename).unwrap();
+        ImageTexture::from_file(filename).unwrap()
+    }
+
+    pub fn from_file(filename: &str) -> io::Result<Self> {
+        let image = image::open(filename).map_err(to_io_error)?;
         if let DynamicImage::ImageRgb8(im) = image {
-            ImageTexture { image: im }
+            Ok(ImageTexture { image: im })
         } else {
-            panic!("Only RGB textures are supported");
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Only RGB textures are supported",
+            ))
         }
     }
 }
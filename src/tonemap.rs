@@ -0,0 +1,95 @@
+use crate::Vec3;
+
+#[derive(Clone, Copy, Debug)]
+pub enum ToneMapping {
+    // Cuts off everything brighter than 1
+    Clamp,
+    // L / (1 + L) on the luminance, which compresses highlights but never reaches white
+    Reinhard,
+    // Reinhard with luminance `white` mapped to 1, the brightest pixel of the image if `None`
+    ExtendedReinhard { white: Option<f32> },
+    // Krzysztof Narkowicz's fit of the ACES filmic curve, with a toe in the shadows and a soft
+    // shoulder in the highlights
+    Aces,
+}
+
+// Turns linear radiance into display values in [0, 1]
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapper {
+    pub tone_mapping: ToneMapping,
+    // In stops, every step of 1 doubles the brightness
+    pub exposure: f32,
+    // Whether to apply the sRGB transfer function for display, leave it off to keep the values
+    // linear
+    pub srgb: bool,
+}
+
+impl ToneMapper {
+    pub fn new(tone_mapping: ToneMapping, exposure: f32, srgb: bool) -> Self {
+        ToneMapper {
+            tone_mapping,
+            exposure,
+            srgb,
+        }
+    }
+
+    // `max_luminance` is the luminance of the brightest pixel in the image before exposure,
+    // only used by the extended Reinhard operator without a white point
+    pub fn map(&self, color: &Vec3, max_luminance: f32) -> Vec3 {
+        let scale = self.exposure.exp2();
+        let color = color * scale;
+        let mapped = match self.tone_mapping {
+            ToneMapping::Clamp => color,
+            ToneMapping::Reinhard => scale_luminance(&color, |l| l / (1. + l)),
+            ToneMapping::ExtendedReinhard { white } => {
+                let white = white.unwrap_or(max_luminance * scale);
+                if white <= 0. {
+                    return Vec3::zeros();
+                }
+                scale_luminance(&color, |l| l * (1. + l / (white * white)) / (1. + l))
+            }
+            ToneMapping::Aces => color.map(aces),
+        };
+        let mapped = mapped.map(|c| c.clamp(0., 1.));
+        if self.srgb {
+            mapped.map(srgb_transfer)
+        } else {
+            mapped
+        }
+    }
+}
+
+impl Default for ToneMapper {
+    // Clamps the values and encodes them as sRGB, which is what 8-bit images are displayed as
+    fn default() -> Self {
+        ToneMapper::new(ToneMapping::Clamp, 0., true)
+    }
+}
+
+pub(crate) fn luminance(color: &Vec3) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+// Scales the color so its luminance becomes `f(luminance)`, which keeps the hue
+fn scale_luminance(color: &Vec3, f: impl Fn(f32) -> f32) -> Vec3 {
+    let l = luminance(color);
+    if l <= 0. {
+        return Vec3::zeros();
+    }
+    color * (f(l) / l)
+}
+
+fn aces(x: f32) -> f32 {
+    // The fit expects the input to be scaled down to match the exposure of the reference curve
+    let x = x * 0.6;
+    let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    (x * (a * x + b)) / (x * (c * x + d) + e)
+}
+
+fn srgb_transfer(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}